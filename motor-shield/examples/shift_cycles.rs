@@ -0,0 +1,149 @@
+// Times one latch update of the 74HC595 with each shift backend and prints
+// its cycle count over the serial console at 57600 baud:
+//
+//   baseline    the loop `DigitalOutput::transmit` ran before the shift
+//               backends were split out
+//   BitBang     the generic embedded-hal bit-bang, the default backend
+//   PortShift   the unrolled port bit-bang of the `fast-shift` feature
+//
+// Timer1 counts CPU cycles (no prescaler) around every call; the cost of the
+// two counter reads is measured once and subtracted.  Build and flash it like
+// the firmware, from the repository root:
+//
+//   cargo run -p motor-shield --example shift_cycles --features fast-shift --release
+//
+// It only needs the Uno.  With the shield plugged in, the test byte is
+// latched but never reaches the motors: output enable stays high.
+#![no_std]
+#![no_main]
+
+use core::hint::black_box;
+
+use arduino_hal::{
+    hal::port,
+    pac::TC1,
+    port::{mode, Pin},
+};
+use motor_shield::{BitBang, PortShift, ShiftOut};
+
+// Calls per backend; the minimum is reported, free of any stray interrupt.
+const RUNS: u8 = 16;
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+
+type Clock = Pin<mode::Output, port::PD4>;
+type Latch = Pin<mode::Output, port::PB4>;
+type Data = Pin<mode::Output, port::PB0>;
+type Enable = Pin<mode::Output, port::PD7>;
+
+// `DigitalOutput::transmit` as it was before `ShiftOut`.
+struct Baseline {
+    clock: Clock,
+    latch: Latch,
+    data: Data,
+}
+
+impl Baseline {
+    #[inline(never)]
+    fn transmit(&mut self, state: u8) {
+        self.latch.set_low();
+
+        for i in 0..8 {
+            self.clock.set_low();
+            if (state & (1 << (7 - i))) != 0 {
+                self.data.set_high();
+            } else {
+                self.data.set_low();
+            }
+            self.clock.set_high();
+        }
+
+        self.latch.set_high();
+    }
+}
+
+#[inline(never)]
+fn shift_out(bus: &mut impl ShiftOut, byte: u8) {
+    bus.shift_out(byte);
+}
+
+// Fewest cycles `f` took over `RUNS` calls.
+fn cycles(tc1: &TC1, overhead: u16, mut f: impl FnMut()) -> u16 {
+    (0..RUNS)
+        .map(|_| {
+            let start = tc1.tcnt1.read().bits();
+            f();
+            let end = tc1.tcnt1.read().bits();
+
+            end.wrapping_sub(start).saturating_sub(overhead)
+        })
+        .min()
+        .unwrap_or(0)
+}
+
+#[arduino_hal::entry]
+fn main() -> ! {
+    let dp = arduino_hal::Peripherals::take().unwrap();
+    let pins = arduino_hal::pins!(dp);
+    let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
+
+    let tc1 = dp.TC1;
+    tc1.tccr1a.reset();
+    tc1.tccr1b.write(|w| w.cs1().direct());
+
+    let overhead = cycles(&tc1, 0, || ());
+    let byte = black_box(0b1010_0101);
+
+    let clock: Clock = pins.d4.into_output_high();
+    let latch: Latch = pins.d12.into_output_high();
+    let data: Data = pins.d8.into_output_high();
+    let enable: Enable = pins.d7.into_output_high();
+
+    let mut baseline = Baseline { clock, latch, data };
+    let baseline_cycles = cycles(&tc1, overhead, || baseline.transmit(byte));
+    let Baseline { clock, latch, data } = baseline;
+
+    let mut bit_bang = BitBang::new(clock, latch, data, enable);
+    let bit_bang_cycles = cycles(&tc1, overhead, || shift_out(&mut bit_bang, byte));
+    let (clock, latch, data, enable) = bit_bang.release();
+
+    let mut port_shift = PortShift::new(clock, latch, data, enable);
+    let port_shift_cycles = cycles(&tc1, overhead, || shift_out(&mut port_shift, byte));
+
+    for (name, count) in [
+        ("baseline  ", baseline_cycles),
+        ("BitBang   ", bit_bang_cycles),
+        ("PortShift ", port_shift_cycles),
+    ] {
+        for byte in name.bytes() {
+            serial.write_byte(byte);
+        }
+
+        // Decimal digits, least significant first.
+        let mut digits = [0u8; 5];
+        let mut len = 0;
+        let mut rest = count;
+
+        loop {
+            digits[len] = b'0' + (rest % 10) as u8;
+            len += 1;
+            rest /= 10;
+
+            if rest == 0 {
+                break;
+            }
+        }
+
+        for &digit in digits[..len].iter().rev() {
+            serial.write_byte(digit);
+        }
+
+        serial.write_byte(b'\r');
+        serial.write_byte(b'\n');
+    }
+
+    loop {}
+}
@@ -1,19 +1,45 @@
 #![no_std]
+// The clock's overflow handlers are `extern "avr-interrupt"` functions.
+#![cfg_attr(feature = "clock", feature(abi_avr_interrupt))]
+
+#[cfg(feature = "sim")]
+extern crate std;
 
 mod motor_shield;
 
-pub use crate::motor_shield::MotorShield;
-pub use crate::motor_shield::layout::{ShieldLayout, MotorPort};
-pub use crate::motor_shield::motors::MotorCommands;
-pub use crate::motor_shield::steppers::{StepperDirection, StepperStyle};
+pub use crate::motor_shield::{MotorShield, ShieldParts};
+pub use crate::motor_shield::board::Board;
+pub use crate::motor_shield::pwm::PwmChannel;
+pub use crate::motor_shield::digital_output::{BitBang, DigitalOutput, ShiftOut};
+pub use crate::motor_shield::layout::{
+    Empty, FirstMotor, MotorPort, Port1Frequency, Port2Frequency, PortLayout, SecondMotor, ShieldLayout,
+    SingleMotorFirst, SingleMotorSecond, SingleStepper, StepperPort, TwoMotors,
+};
+pub use crate::motor_shield::error::{HomingError, ShieldError};
+pub use crate::motor_shield::motors::{Motor, MotorChannel, MotorCommands, MotorId, StopMode, MAX_VELOCITY};
+pub use crate::motor_shield::steppers::{
+    HomingConfig, Stepper, StepperChannel, StepperConfig, StepperDirection, StepperHold, StepperId, StepperStyle,
+};
+pub use crate::motor_shield::microstep::{MicrostepCurve, MicrostepResolution, CURVE_POINTS, SINE_CURVE};
+pub use crate::motor_shield::servos::{Easing, Servo, ServoId, SERVO_PERIOD_US};
+pub use crate::motor_shield::sequencer::{Keyframe, ServoSequence};
+#[cfg(feature = "arduino-uno")]
+pub use crate::motor_shield::uno::{ArduinoUno, UnoLeftovers, UnoMotorPwm, UnoServoPwm, UnoShiftOut, UnoTimers};
+#[cfg(feature = "fast-shift")]
+pub use crate::motor_shield::port_shift::PortShift;
+#[cfg(feature = "clock")]
+pub use crate::motor_shield::clock::Clock;
+#[cfg(feature = "sim")]
+pub use crate::motor_shield::sim::{SimBoard, SimChannel, SimEvent, SimPwm, SimRecord, SimShield, SimShift, Trace};
 
+#[cfg(feature = "arduino-uno")]
 #[macro_export]
 macro_rules! init_ams {
     ($layout:expr, $p:expr, $pins:expr) => {
         MotorShield::new(
             $layout,
-            $p.TC0, $p.TC1, $p.TC2, 
+            $p.TC0, $p.TC1, $p.TC2,
             $pins.d3, $pins.d4, $pins.d5, $pins.d6, $pins.d7, $pins.d8, $pins.d9, $pins.d10, $pins.d11, $pins.d12,
         )
     };
-}
\ No newline at end of file
+}
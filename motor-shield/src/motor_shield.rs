@@ -2,112 +2,120 @@
 pub mod motors;
 pub mod steppers;
 pub mod servos;
-mod digital_output;
+pub mod sequencer;
+pub mod digital_output;
 pub mod layout;
+pub mod error;
+pub mod pwm;
+pub mod microstep;
+mod ramp;
+pub mod board;
+#[cfg(feature = "arduino-uno")]
+pub mod uno;
+#[cfg(feature = "fast-shift")]
+pub mod port_shift;
+#[cfg(feature = "clock")]
+pub mod clock;
+#[cfg(feature = "sim")]
+pub mod sim;
 
-use arduino_hal::{
-    hal::port,
-    pac::{TC0, TC1, TC2},
-    port::{mode, Pin},
-    simple_pwm::{IntoPwmPin, Prescaler, Timer0Pwm, Timer1Pwm, Timer2Pwm}
-};
+use core::marker::PhantomData;
+
+use embedded_hal::delay::DelayNs;
 
 use crate::motor_shield::layout::ShieldLayout;
 
-use self::{layout::{MotorPort, Steppers, Motors, Servos}, motors::{MotorPin, Motor}, steppers::{StepperPin, Stepper}, servos::{ServoPin, Servo}, digital_output::DigitalOutput};
+use self::{board::Board, pwm::PwmChannel, layout::{FirstMotor, MotorPort, PortLayout, SecondMotor, StepperPort, Steppers, Motors, Servos}, motors::{MotorCommands, MotorId, MotorChannel, Motor, StopMode}, steppers::{StepperDirection, StepperId, StepperChannel, Stepper, StepperStyle}, servos::{ServoId, Servo}, digital_output::DigitalOutput, error::ShieldError};
+
+// Drivers built for one motor port: a stepper, or up to two DC motors.
+type PortDrivers<P> = (
+    Option<StepperChannel<P>>,
+    Option<MotorChannel<P>>,
+    Option<MotorChannel<P>>,
+);
+
+// Everything `MotorShield::from_parts` takes apart from the layout, as handed
+// back by `MotorShield::into_parts`.
+pub type ShieldParts<B> = (
+    <B as Board>::Shift,
+    <B as Board>::Timers,
+    [Option<<B as Board>::MotorPwm>; 4],
+    [Option<<B as Board>::ServoPwm>; 2],
+);
 
-pub struct MotorShield {
-    steppers: Steppers,
-    motors: Motors,
-    servos: Servos,
+// A shield on board `B` whose ports are laid out as `P1` and `P2`: run time
+// `MotorPort`s by default, or the `PortLayout` markers to fix them in the type.
+pub struct MotorShield<B: Board, P1 = MotorPort, P2 = MotorPort> {
+    // The one 74HC595 shared by every motor and stepper.
+    output: DigitalOutput<B::Shift>,
+    steppers: Steppers<B>,
+    motors: Motors<B>,
+    servos: Servos<B>,
+    timers: B::Timers,
+    ports: PhantomData<(P1, P2)>,
 }
 
-impl MotorShield {
-    pub fn new(
-        layout: ShieldLayout,
-        tc0: TC0,
-        tc1: TC1,
-        tc2: TC2,
-        pin_d3: Pin<mode::Input<mode::Floating>, port::PD3>,
-        pin_d4: Pin<mode::Input<mode::Floating>, port::PD4>,
-        pin_d5: Pin<mode::Input<mode::Floating>, port::PD5>,
-        pin_d6: Pin<mode::Input<mode::Floating>, port::PD6>,
-        pin_d7: Pin<mode::Input<mode::Floating>, port::PD7>,
-        pin_d8: Pin<mode::Input<mode::Floating>, port::PB0>,
-        pin_d9: Pin<mode::Input<mode::Floating>, port::PB1>,
-        pin_d10: Pin<mode::Input<mode::Floating>, port::PB2>,
-        pin_d11: Pin<mode::Input<mode::Floating>, port::PB3>,
-        pin_d12: Pin<mode::Input<mode::Floating>, port::PB4>,
+impl<B: Board, P1: PortLayout, P2: PortLayout> MotorShield<B, P1, P2> {
+    // Builds a shield from already configured outputs.  `motor_pwm` holds the
+    // D11, D3, D6 and D5 channels, `servo_pwm` the D10 and D9 channels; a
+    // channel may be `None` when the layout leaves it unused, and is dropped
+    // when the layout does.  Fails if a motor or stepper of the layout is
+    // missing a channel, so the accessors of a typed layout always find
+    // their outputs.
+    pub fn from_parts(
+        layout: ShieldLayout<P1, P2>,
+        shift: B::Shift,
+        timers: B::Timers,
+        motor_pwm: [Option<B::MotorPwm>; 4],
+        servo_pwm: [Option<B::ServoPwm>; 2],
+    ) -> Result<Self, ShieldError> {
+        let ports = [
+            (layout.port1.port(), StepperId::S1, [MotorId::M1, MotorId::M2]),
+            (layout.port2.port(), StepperId::S2, [MotorId::M3, MotorId::M4]),
+        ];
+
+        for ((port, stepper, motors), pins) in ports.into_iter().zip(motor_pwm.chunks(2)) {
+            let used = [port.uses_first(), port.uses_second()];
+
+            for ((used, pin), motor) in used.into_iter().zip(pins).zip(motors) {
+                if used && pin.is_none() {
+                    return Err(match port {
+                        MotorPort::SingleStepper(_) => ShieldError::StepperChannelMissing(stepper),
+                        _ => ShieldError::MotorChannelMissing(motor),
+                    });
+                }
+            }
+        }
+
+        Ok(Self::build(layout, shift, timers, motor_pwm, servo_pwm))
+    }
+
+    // `from_parts` for callers that hand over every channel the layout uses.
+    pub(crate) fn build(
+        layout: ShieldLayout<P1, P2>,
+        shift: B::Shift,
+        timers: B::Timers,
+        motor_pwm: [Option<B::MotorPwm>; 4],
+        servo_pwm: [Option<B::ServoPwm>; 2],
     ) -> Self {
-        let mut pwm_timer0 = Timer0Pwm::new(tc0, Prescaler::Prescale64);
-        let mut pwm_timer1 = Timer1Pwm::new(tc1, Prescaler::Prescale256);
-        let mut pwm_timer2 = Timer2Pwm::new(tc2, Prescaler::Prescale64);
-
-        let digital_output = &mut DigitalOutput::new(pin_d4, pin_d7, pin_d8, pin_d12);
-
-        let (s1, m1, m2) = match layout.port1 {
-            MotorPort::SingleStepper => (
-                Some(Stepper::new(
-                    StepperPin::Stepper1((
-                        pin_d11.into_output().into_pwm(&mut pwm_timer2),
-                        pin_d3.into_output().into_pwm(&mut pwm_timer2),
-                    )),
-                    48,
-                    digital_output,
-                )),
-                None,
-                None,
-            ),
-            MotorPort::SingleMotorFirst | MotorPort::SingleMotorSecond | MotorPort::TwoMotors => {
-                let motor1 = if matches!(layout.port1, MotorPort::SingleMotorFirst | MotorPort::TwoMotors) {
-                    Some(Motor::new(MotorPin::Motor1(pin_d11.into_output().into_pwm(&mut pwm_timer2)), digital_output))
-                } else {
-                    None
-                };
-
-                let motor2 = if matches!(layout.port1, MotorPort::SingleMotorSecond | MotorPort::TwoMotors) {
-                    Some(Motor::new(MotorPin::Motor2(pin_d3.into_output().into_pwm(&mut pwm_timer2)), digital_output))
-                } else {
-                    None
-                };
-
-                (None, motor1, motor2)
-            },
-            _ => (None, None, None),
-        };
+        let [d11, d3, d6, d5] = motor_pwm;
+        let [d10, d9] = servo_pwm;
+        let (servo1, servo2) = (layout.servo1, layout.servo2);
 
-        let (s2, m3, m4) = match layout.port2 {
-            MotorPort::SingleStepper => (
-                Some(Stepper::new(
-                    StepperPin::Stepper2((
-                        pin_d6.into_output().into_pwm(&mut pwm_timer0),
-                        pin_d5.into_output().into_pwm(&mut pwm_timer0),
-                    )),
-                    48,
-                    digital_output,
-                )),
-                None,
-                None,
-            ),
-            MotorPort::SingleMotorFirst | MotorPort::SingleMotorSecond | MotorPort::TwoMotors => {
-                let motor3 = if matches!(layout.port2, MotorPort::SingleMotorFirst | MotorPort::TwoMotors) {
-                    Some(Motor::new(MotorPin::Motor3(pin_d6.into_output().into_pwm(&mut pwm_timer0)), digital_output))
-                } else {
-                    None
-                };
-
-                let motor4 = if matches!(layout.port2, MotorPort::SingleMotorSecond | MotorPort::TwoMotors) {
-                    Some(Motor::new(MotorPin::Motor4(pin_d5.into_output().into_pwm(&mut pwm_timer0)), digital_output))
-                } else {
-                    None
-                };
-
-                (None, motor3, motor4)
-            },
-            _ => (None, None, None),
-        };
+        let (s1, m1, m2) = Self::build_port(
+            layout.port1.port(),
+            (d11, d3),
+            (StepperId::S1, MotorId::M1, MotorId::M2),
+        );
+
+        let (s2, m3, m4) = Self::build_port(
+            layout.port2.port(),
+            (d6, d5),
+            (StepperId::S2, MotorId::M3, MotorId::M4),
+        );
 
-        Self {
+        let mut me = Self {
+            output: DigitalOutput::new(shift),
             steppers: Steppers {
                 s1,
                 s2
@@ -119,9 +127,78 @@ impl MotorShield {
                 m4,
             },
             servos: Servos {
-                s1: Some(Servo::new(ServoPin::Servo1(pin_d10.into_output().into_pwm(&mut pwm_timer1)))),
-                s2: Some(Servo::new(ServoPin::Servo2( pin_d9.into_output().into_pwm(&mut pwm_timer1))))
+                s1: d10.filter(|_| servo1).map(Servo::new),
+                s2: d9.filter(|_| servo2).map(Servo::new),
             },
+            timers,
+            ports: PhantomData,
+        };
+
+        for id in StepperId::ALL {
+            if let Some(mut stepper) = me.stepper(id) {
+                stepper.init();
+            }
+        }
+
+        me
+    }
+
+    // Takes the shield apart again, leaving it safe: every PWM channel at zero
+    // duty and disabled, then the latch cleared so no bridge is driven.
+    pub fn into_parts(self) -> ShieldParts<B> {
+        let Self { mut output, steppers, motors, servos, timers, .. } = self;
+
+        let (d11, d3) = Self::port_pins(steppers.s1, motors.m1, motors.m2);
+        let (d6, d5) = Self::port_pins(steppers.s2, motors.m3, motors.m4);
+        let mut motor_pwm = [d11, d3, d6, d5];
+        let mut servo_pwm = [servos.s1.map(Servo::into_pin), servos.s2.map(Servo::into_pin)];
+
+        for pin in motor_pwm.iter_mut().flatten() {
+            release_pwm(pin);
+        }
+
+        for pin in servo_pwm.iter_mut().flatten() {
+            release_pwm(pin);
+        }
+
+        output.and(0);
+        output.transmit();
+
+        (output.into_bus(), timers, motor_pwm, servo_pwm)
+    }
+
+    fn port_pins(
+        stepper: Option<StepperChannel<B::MotorPwm>>,
+        first: Option<MotorChannel<B::MotorPwm>>,
+        second: Option<MotorChannel<B::MotorPwm>>,
+    ) -> (Option<B::MotorPwm>, Option<B::MotorPwm>) {
+        match stepper.map(StepperChannel::into_pins) {
+            Some((a, b)) => (Some(a), Some(b)),
+            None => (first.map(MotorChannel::into_pin), second.map(MotorChannel::into_pin)),
+        }
+    }
+
+    fn build_port(
+        port: MotorPort,
+        (first, second): (Option<B::MotorPwm>, Option<B::MotorPwm>),
+        (stepper, motor_first, motor_second): (StepperId, MotorId, MotorId),
+    ) -> PortDrivers<B::MotorPwm> {
+        match port {
+            MotorPort::SingleStepper(config) => match (first, second) {
+                (Some(a), Some(b)) => (Some(StepperChannel::new((a, b), stepper, config)), None, None),
+                _ => (None, None, None),
+            },
+            MotorPort::SingleMotorFirst | MotorPort::SingleMotorSecond | MotorPort::TwoMotors => {
+                let first = first.filter(|_| port.uses_first());
+                let second = second.filter(|_| port.uses_second());
+
+                (
+                    None,
+                    first.map(|pin| MotorChannel::new(pin, motor_first)),
+                    second.map(|pin| MotorChannel::new(pin, motor_second)),
+                )
+            },
+            _ => (None, None, None),
         }
     }
 
@@ -129,53 +206,295 @@ impl MotorShield {
         self.steppers.len()
     }
 
-    pub fn stepper(&mut self, stepper_id: usize) -> Option<&mut Stepper> {
-        match stepper_id {
-            1 => self.steppers.s1.as_mut(),
-            2 => self.steppers.s2.as_mut(),
-            _ => panic!("invalid stepper index")
+    pub fn stepper(&mut self, stepper_id: StepperId) -> Option<Stepper<'_, B::MotorPwm, B::Shift>> {
+        let channel = match stepper_id {
+            StepperId::S1 => self.steppers.s1.as_mut(),
+            StepperId::S2 => self.steppers.s2.as_mut(),
+        };
+
+        channel.map(|channel| Stepper::new(channel, &mut self.output))
+    }
+
+    // Polls both steppers at `now_us`, latching their steps together.
+    // Returns whether either is still moving.
+    pub fn poll_steppers(&mut self, now_us: u32) -> bool {
+        self.batch(|tx| {
+            StepperId::ALL.into_iter().fold(false, |moving, id| {
+                let polled = tx.stepper(id).is_some_and(|mut stepper| stepper.poll(now_us));
+
+                moving | polled
+            })
+        })
+    }
+
+    // Moves stepper 1 by `dx` and stepper 2 by `dy` full steps along a
+    // straight line, so both arrive together: the longer axis steps at
+    // `speed` full steps per second and the other is interleaved with it
+    // Bresenham-style, with simultaneous steps latched together.  Blocks
+    // until done; an axis needing more than `u32::MAX` `onestep` calls stops
+    // there.  Nothing moves if a stepper that has to is not configured by
+    // the layout.
+    pub fn move_steppers_linear(
+        &mut self,
+        dx: i32,
+        dy: i32,
+        speed: u16,
+        style: StepperStyle,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), ShieldError> {
+        let axes = [(StepperId::S1, dx), (StepperId::S2, dy)];
+
+        if let Some(&(id, _)) = axes.iter().find(|&&(id, d)| d != 0 && self.stepper(id).is_none()) {
+            return Err(ShieldError::StepperNotConfigured(id));
         }
+
+        // `onestep` calls and their rate per axis, which differ in MICROSTEP
+        // style when the steppers use different resolutions.
+        let mut counts = [0; 2];
+        let mut rates = [1; 2];
+
+        for ((&(id, d), count), rate) in axes.iter().zip(&mut counts).zip(&mut rates) {
+            if let Some(stepper) = self.stepper(id) {
+                let microsteps = stepper.microsteps_per_step();
+
+                *count = style.onesteps(d.unsigned_abs(), microsteps);
+                *rate = style.rate(microsteps);
+            }
+        }
+
+        let [nx, ny] = counts;
+        let major = nx.max(ny);
+        let wait_us = 1_000_000 / (speed.max(1) as u32 * rates[if nx >= ny { 0 } else { 1 }]);
+
+        let dir = |d: i32| if d < 0 { StepperDirection::BACKWARD } else { StepperDirection::FORWARD };
+        let mut errors = [major / 2; 2];
+
+        for _ in 0..major {
+            self.batch(|tx| {
+                for ((&(id, d), n), error) in axes.iter().zip([nx, ny]).zip(&mut errors) {
+                    // `error + n >= major`, without overflowing for the
+                    // longest moves.
+                    if *error >= major - n {
+                        *error -= major - n;
+
+                        if let Some(mut stepper) = tx.stepper(id) {
+                            stepper.onestep(dir(d), style);
+                        }
+                    } else {
+                        *error += n;
+                    }
+                }
+            });
+
+            delay.delay_us(wait_us);
+        }
+
+        Ok(())
     }
 
     pub fn motors_count(&mut self) -> usize {
         self.motors.len()
     }
 
-    pub fn motor(&mut self, motor_id: usize) -> Option<&mut Motor> {
-        match motor_id {
-            1 => self.motors.m1.as_mut(),
-            2 => self.motors.m2.as_mut(),
-            3 => self.motors.m3.as_mut(),
-            4 => self.motors.m4.as_mut(),
-            _ => panic!("invalid motor index")
-        }
+    pub fn motor(&mut self, motor_id: MotorId) -> Option<Motor<'_, B::MotorPwm, B::Shift>> {
+        let channel = match motor_id {
+            MotorId::M1 => self.motors.m1.as_mut(),
+            MotorId::M2 => self.motors.m2.as_mut(),
+            MotorId::M3 => self.motors.m3.as_mut(),
+            MotorId::M4 => self.motors.m4.as_mut(),
+        };
+
+        channel.map(|channel| Motor::new(channel, &mut self.output))
     }
 
     pub fn servos_count(&mut self) -> usize {
         self.servos.len()
     }
 
-    pub fn servo(&mut self, servo_id: usize) -> Option<&mut Servo> {
+    pub fn servo(&mut self, servo_id: ServoId) -> Option<&mut Servo<B::ServoPwm>> {
         match servo_id {
-            1 => self.servos.s1.as_mut(),
-            2 => self.servos.s2.as_mut(),
-            _ => panic!("invalid servo index")
+            ServoId::S1 => self.servos.s1.as_mut(),
+            ServoId::S2 => self.servos.s2.as_mut(),
+        }
+    }
+
+    // Advances the moves of both servos by `dt_ms`.  Returns whether either
+    // is still moving.
+    pub fn update_servos(&mut self, dt_ms: u16) -> bool {
+        ServoId::ALL.into_iter().fold(false, |moving, id| {
+            let updated = self.servo(id).is_some_and(|servo| servo.update(dt_ms));
+
+            moving | updated
+        })
+    }
+
+    // Fails unless every motor in `motor_ids` is configured by the layout.
+    fn check_motors(&mut self, mut motor_ids: impl Iterator<Item = MotorId>) -> Result<(), ShieldError> {
+        match motor_ids.find(|&id| self.motor(id).is_none()) {
+            Some(id) => Err(ShieldError::MotorNotConfigured(id)),
+            None => Ok(()),
         }
     }
 
-    pub fn enable_motors(&mut self, motor_ids: &[usize]) {
+    // Runs `f` with every latch update staged, then latches the result with a
+    // single transmit, skipped when the byte did not change.  PWM duties are
+    // not staged and change immediately.
+    pub fn batch<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let nested = self.output.stage();
+        let result = f(self);
+        self.output.commit(nested);
+
+        result
+    }
+
+    // Enables the given motors.  Nothing changes if any of them is not
+    // configured by the layout.
+    pub fn enable_motors(&mut self, motor_ids: &[MotorId]) -> Result<(), ShieldError> {
+        self.check_motors(motor_ids.iter().copied())?;
+
         for &id in motor_ids {
-            if let Some(motor) = self.motor(id) {
+            if let Some(mut motor) = self.motor(id) {
                 motor.enable();
             }
         }
+
+        Ok(())
     }
 
-    pub fn set_speeds(&mut self, motor_speeds: &[(usize, u8)]) {
-        for &(id, speed) in motor_speeds {
+    // Sets the velocity of the given motors, as `Motor::set_velocity` does.
+    // Motors without an acceleration limit change together: every reversing
+    // motor first drops to zero duty, then all direction bits are latched
+    // with one transmit, then the new duties are applied.  Nothing changes if
+    // any of the motors is not configured by the layout.
+    pub fn set_velocities(&mut self, motor_velocities: &[(MotorId, i16)]) -> Result<(), ShieldError> {
+        self.check_motors(motor_velocities.iter().map(|&(id, _)| id))?;
+
+        for &(id, velocity) in motor_velocities {
+            if let Some(mut motor) = self.motor(id) {
+                motor.set_target(velocity);
+            }
+        }
+
+        self.update(0);
+
+        Ok(())
+    }
+
+    // Advances every motor by `dt` ticks towards its target velocity, by at
+    // most its acceleration limit per tick.  Motors that change are applied
+    // like `set_velocities`, with a single latch update.
+    pub fn update(&mut self, dt: u16) {
+        let mut velocities = [None; 4];
+
+        for (velocity, id) in velocities.iter_mut().zip(MotorId::ALL) {
             if let Some(motor) = self.motor(id) {
+                let next = motor.next_velocity(dt);
+
+                if next != motor.velocity() {
+                    *velocity = Some(next);
+                }
+            }
+        }
+
+        let changes = || MotorId::ALL.into_iter().zip(velocities).filter_map(|(id, v)| Some((id, v?)));
+
+        self.batch(|tx| {
+            for (id, velocity) in changes() {
+                if let Some(mut motor) = tx.motor(id) {
+                    motor.set_direction(velocity);
+                }
+            }
+        });
+
+        for (id, velocity) in changes() {
+            if let Some(mut motor) = self.motor(id) {
+                motor.drive(velocity);
+            }
+        }
+    }
+
+    // Stops every configured motor with one latch update, ordered like
+    // `Motor::stop`.
+    pub fn stop_all(&mut self, mode: StopMode) {
+        let (command, duty) = match mode {
+            StopMode::Coast => (MotorCommands::RELEASE, 0),
+            StopMode::Brake => (MotorCommands::BRAKE, u8::MAX),
+        };
+
+        if mode == StopMode::Coast {
+            self.for_each_motor(|motor| motor.speed(duty));
+        }
+
+        self.batch(|tx| tx.for_each_motor(|motor| motor.run(command)));
+
+        if mode == StopMode::Brake {
+            self.for_each_motor(|motor| motor.speed(duty));
+        }
+    }
+
+    fn for_each_motor(&mut self, mut f: impl FnMut(&mut Motor<'_, B::MotorPwm, B::Shift>)) {
+        for id in MotorId::ALL {
+            if let Some(mut motor) = self.motor(id) {
+                f(&mut motor);
+            }
+        }
+    }
+
+    // Sets the duty of the given motors.  Nothing changes if any of them is
+    // not configured by the layout.
+    pub fn set_speeds(&mut self, motor_speeds: &[(MotorId, u8)]) -> Result<(), ShieldError> {
+        self.check_motors(motor_speeds.iter().map(|&(id, _)| id))?;
+
+        for &(id, speed) in motor_speeds {
+            if let Some(mut motor) = self.motor(id) {
                 motor.speed(speed);
             }
         }
+
+        Ok(())
+    }
+}
+
+// Accessors for the outputs fixed by a `PortLayout` marker.  The layout
+// guarantees them: every way of building a shield hands over their channels,
+// which `from_parts` checks.
+impl<B: Board, P1: FirstMotor, P2: PortLayout> MotorShield<B, P1, P2> {
+    pub fn m1(&mut self) -> Motor<'_, B::MotorPwm, B::Shift> {
+        self.motor(MotorId::M1).expect("M1 built for the layout")
     }
-}
\ No newline at end of file
+}
+
+impl<B: Board, P1: SecondMotor, P2: PortLayout> MotorShield<B, P1, P2> {
+    pub fn m2(&mut self) -> Motor<'_, B::MotorPwm, B::Shift> {
+        self.motor(MotorId::M2).expect("M2 built for the layout")
+    }
+}
+
+impl<B: Board, P1: PortLayout, P2: FirstMotor> MotorShield<B, P1, P2> {
+    pub fn m3(&mut self) -> Motor<'_, B::MotorPwm, B::Shift> {
+        self.motor(MotorId::M3).expect("M3 built for the layout")
+    }
+}
+
+impl<B: Board, P1: PortLayout, P2: SecondMotor> MotorShield<B, P1, P2> {
+    pub fn m4(&mut self) -> Motor<'_, B::MotorPwm, B::Shift> {
+        self.motor(MotorId::M4).expect("M4 built for the layout")
+    }
+}
+
+impl<B: Board, P1: StepperPort, P2: PortLayout> MotorShield<B, P1, P2> {
+    pub fn stepper1(&mut self) -> Stepper<'_, B::MotorPwm, B::Shift> {
+        self.stepper(StepperId::S1).expect("stepper 1 built for the layout")
+    }
+}
+
+impl<B: Board, P1: PortLayout, P2: StepperPort> MotorShield<B, P1, P2> {
+    pub fn stepper2(&mut self) -> Stepper<'_, B::MotorPwm, B::Shift> {
+        self.stepper(StepperId::S2).expect("stepper 2 built for the layout")
+    }
+}
+
+fn release_pwm(pin: &mut impl PwmChannel) {
+    let _ = pin.set_duty_cycle(0);
+    pin.disable();
+}
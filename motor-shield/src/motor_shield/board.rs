@@ -0,0 +1,13 @@
+use super::{digital_output::ShiftOut, pwm::PwmChannel};
+
+// The concrete pin types a shield is wired to.
+pub trait Board {
+    // L293D enable channels: D11, D3 (port 1) and D6, D5 (port 2) on the Uno.
+    type MotorPwm: PwmChannel;
+    // Servo headers: D10 and D9 on the Uno.
+    type ServoPwm: PwmChannel;
+    // Transport behind the 74HC595 latch.
+    type Shift: ShiftOut;
+    // Timers the PWM channels run on, held by the shield until `into_parts`.
+    type Timers;
+}
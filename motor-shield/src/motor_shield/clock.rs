@@ -0,0 +1,111 @@
+use core::cell::Cell;
+
+use avr_device::interrupt::{self, Mutex};
+
+#[cfg(feature = "clock-timer0")]
+use super::uno::timer0;
+#[cfg(not(feature = "clock-timer0"))]
+use super::uno::timer2;
+
+// Time kept by the overflow interrupt: microseconds up to the last overflow,
+// and whole milliseconds plus the microseconds towards the next one.
+#[derive(Clone, Copy)]
+struct Ticks {
+    micros: u32,
+    millis: u32,
+    fract_us: u16,
+    overflow_us: u16,
+}
+
+static TICKS: Mutex<Cell<Ticks>> = Mutex::new(Cell::new(Ticks {
+    micros: 0,
+    millis: 0,
+    fract_us: 0,
+    overflow_us: 0,
+}));
+
+// Monotonic time since `MotorShield::start_clock`, counted by the overflow
+// interrupt of a motor PWM timer: Timer2, or Timer0 with the `clock-timer0`
+// feature.  Only the overflow interrupt is enabled, so the PWM duties are
+// untouched, and the crate only defines the vector of that one timer.  Reads
+// are interrupt-safe and may be made from any context, interrupt handlers
+// included.  Global interrupts must be enabled for the clock to advance.
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    overflow_us: u16,
+}
+
+impl Clock {
+    // Restarts the count from zero and enables the overflow interrupt of the
+    // clock timer, which has to be running with one overflow every
+    // `overflow_us`.
+    pub(crate) fn start(overflow_us: u16) -> Self {
+        interrupt::free(|cs| {
+            TICKS.borrow(cs).set(Ticks { micros: 0, millis: 0, fract_us: 0, overflow_us });
+        });
+
+        #[cfg(not(feature = "clock-timer0"))]
+        timer2().timsk2.modify(|_, w| w.toie2().set_bit());
+        #[cfg(feature = "clock-timer0")]
+        timer0().timsk0.modify(|_, w| w.toie0().set_bit());
+
+        Clock { overflow_us }
+    }
+
+    // Microseconds, wrapping after about 71 minutes like `poll_steppers`
+    // expects.  Resolves to a timer count: 4 us at 1 kHz PWM.
+    pub fn now_us(&self) -> u32 {
+        interrupt::free(|cs| {
+            let ticks = TICKS.borrow(cs).get();
+
+            #[cfg(not(feature = "clock-timer0"))]
+            let (count, overflowed) = (timer2().tcnt2.read().bits(), timer2().tifr2.read().tov2().bit_is_set());
+            #[cfg(feature = "clock-timer0")]
+            let (count, overflowed) = (timer0().tcnt0.read().bits(), timer0().tifr0.read().tov0().bit_is_set());
+
+            // An overflow while interrupts were off has not been counted yet,
+            // unless the count read before it.
+            let micros = if overflowed && count < u8::MAX {
+                ticks.micros.wrapping_add(self.overflow_us as u32)
+            } else {
+                ticks.micros
+            };
+
+            micros.wrapping_add(count as u32 * self.overflow_us as u32 / 256)
+        })
+    }
+
+    // Milliseconds, wrapping after about 49 days.
+    pub fn now_ms(&self) -> u32 {
+        interrupt::free(|cs| TICKS.borrow(cs).get().millis)
+    }
+}
+
+fn tick() {
+    interrupt::free(|cs| {
+        let cell = TICKS.borrow(cs);
+        let mut ticks = cell.get();
+
+        ticks.micros = ticks.micros.wrapping_add(ticks.overflow_us as u32);
+        ticks.fract_us += ticks.overflow_us;
+
+        while ticks.fract_us >= 1000 {
+            ticks.millis = ticks.millis.wrapping_add(1);
+            ticks.fract_us -= 1000;
+        }
+
+        cell.set(ticks);
+    });
+}
+
+#[cfg(not(feature = "clock-timer0"))]
+#[avr_device::interrupt(atmega328p)]
+fn TIMER2_OVF() {
+    tick();
+}
+
+#[cfg(feature = "clock-timer0")]
+#[avr_device::interrupt(atmega328p)]
+fn TIMER0_OVF() {
+    tick();
+}
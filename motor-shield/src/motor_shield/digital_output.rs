@@ -1,33 +1,87 @@
-use arduino_hal::{
-    hal::port,
-    port::{mode, Pin}
-};
-
-pub struct DigitalOutput {
-    enable: Pin<mode::Output, port::PD7>,
-    latch: Pin<mode::Output, port::PB4>,
-    data: Pin<mode::Output, port::PB0>,
-    clock: Pin<mode::Output, port::PD4>,
-    state: u8
+use embedded_hal::digital::{OutputPin, PinState};
+
+// Transport that moves a byte into the 74HC595 shift register.
+pub trait ShiftOut {
+    // Clock `byte` into the register MSB first and latch it onto the outputs.
+    fn shift_out(&mut self, byte: u8);
+    // Drive the active-low output enable so the latched byte reaches the L293Ds.
+    fn enable_outputs(&mut self);
+}
+
+// Software bit-bang over four GPIOs: clock (D4), latch (D12), data (D8) and enable (D7).
+pub struct BitBang<CLK, LATCH, DATA, EN> {
+    clock: CLK,
+    latch: LATCH,
+    data: DATA,
+    enable: EN,
+}
+
+impl<CLK, LATCH, DATA, EN> BitBang<CLK, LATCH, DATA, EN>
+where
+    CLK: OutputPin,
+    LATCH: OutputPin,
+    DATA: OutputPin,
+    EN: OutputPin,
+{
+    pub fn new(clock: CLK, latch: LATCH, data: DATA, enable: EN) -> Self {
+        Self {
+            clock,
+            latch,
+            data,
+            enable,
+        }
+    }
+
+    // Hands back the clock, latch, data and enable pins.
+    pub fn release(self) -> (CLK, LATCH, DATA, EN) {
+        (self.clock, self.latch, self.data, self.enable)
+    }
 }
 
-impl DigitalOutput {
-    pub fn new(
-        pin_d4: Pin<mode::Input<mode::Floating>, port::PD4>,
-        pin_d7: Pin<mode::Input<mode::Floating>, port::PD7>,
-        pin_d8: Pin<mode::Input<mode::Floating>, port::PB0>,
-        pin_d12: Pin<mode::Input<mode::Floating>, port::PB4>,
-    ) -> Self {
+impl<CLK, LATCH, DATA, EN> ShiftOut for BitBang<CLK, LATCH, DATA, EN>
+where
+    CLK: OutputPin,
+    LATCH: OutputPin,
+    DATA: OutputPin,
+    EN: OutputPin,
+{
+    fn shift_out(&mut self, byte: u8) {
+        let _ = self.latch.set_low();
+
+        for i in 0..8 {
+            let _ = self.clock.set_low();
+            let _ = self.data.set_state(PinState::from((byte & (1 << (7 - i))) != 0));
+            let _ = self.clock.set_high();
+        }
+
+        let _ = self.latch.set_high();
+    }
+
+    fn enable_outputs(&mut self) {
+        let _ = self.enable.set_low();
+    }
+}
+
+pub struct DigitalOutput<T> {
+    bus: T,
+    state: u8,
+    // Byte currently on the register outputs.
+    latched: u8,
+    // Set while a batch holds back transmits.
+    staging: bool,
+}
+
+impl<T: ShiftOut> DigitalOutput<T> {
+    pub fn new(bus: T) -> Self {
         let mut me = Self {
-            enable: pin_d7.into_output_high(),
-            latch: pin_d12.into_output_high(),
-            data: pin_d8.into_output_high(),
-            clock: pin_d4.into_output_high(),
+            bus,
             state: 0,
+            latched: 0,
+            staging: false,
         };
 
         me.transmit();
-        me.enable.set_low();
+        me.bus.enable_outputs();
 
         me
     }
@@ -44,39 +98,35 @@ impl DigitalOutput {
     }
 
     pub fn transmit(&mut self) {
-        self.latch.set_low();
-
-        for i in 0..8 {
-            self.clock.set_low();
-            self.data.set_state((self.state & (1 << (7 - i))) != 0);
-            self.clock.set_high();
+        if !self.staging {
+            self.latch();
         }
+    }
 
-        self.latch.set_high();
+    // Holds back transmits until the matching `commit`.  Returns whether a
+    // batch was already open, to be handed back to `commit`.
+    pub(crate) fn stage(&mut self) -> bool {
+        core::mem::replace(&mut self.staging, true)
     }
 
-}
-// Extension for the `Pin` to simplify the setting of the pin state.
-trait PinExt {
-    fn set_state(&mut self, high: bool);
-}
+    // Closes a batch; the outermost one latches the staged byte, unless it
+    // is already on the outputs.
+    pub(crate) fn commit(&mut self, nested: bool) {
+        self.staging = nested;
 
-impl PinExt for Pin<mode::Output, port::PD4> {
-    fn set_state(&mut self, high: bool) {
-        if high {
-            self.set_high();
-        } else {
-            self.set_low();
+        if !nested && self.state != self.latched {
+            self.latch();
         }
     }
-}
 
-impl PinExt for Pin<mode::Output, port::PB0> {
-    fn set_state(&mut self, high: bool) {
-        if high {
-            self.set_high();
-        } else {
-            self.set_low();
-        }
+    // Hands back the transport, leaving the latched byte on the outputs.
+    pub(crate) fn into_bus(self) -> T {
+        self.bus
     }
-}
\ No newline at end of file
+
+    fn latch(&mut self) {
+        self.bus.shift_out(self.state);
+        self.latched = self.state;
+    }
+
+}
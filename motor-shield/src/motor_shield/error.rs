@@ -0,0 +1,26 @@
+use super::{motors::MotorId, steppers::StepperId};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ShieldError {
+    // The layout puts no DC motor on this output.
+    MotorNotConfigured(MotorId),
+    // The layout puts no stepper on this port.
+    StepperNotConfigured(StepperId),
+    // `MotorShield::from_parts` got no PWM channel for this motor.
+    MotorChannelMissing(MotorId),
+    // `MotorShield::from_parts` got no PWM channel for a coil of this
+    // stepper.
+    StepperChannelMissing(StepperId),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HomingError {
+    // The endstop was already triggered before homing started.
+    AlreadyTriggered,
+    // The endstop did not trigger within the allowed travel.
+    TravelExceeded,
+    // The endstop stayed triggered after backing off.
+    NotReleased,
+    // The endstop pin could not be read.
+    Endstop,
+}
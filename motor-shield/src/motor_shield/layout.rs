@@ -1,44 +1,170 @@
-use super::{steppers::Stepper, servos::Servo, motors::Motor};
-
+use super::{board::Board, steppers::{StepperChannel, StepperConfig}, servos::Servo, motors::MotorChannel};
 
+#[derive(Clone, Copy)]
 pub enum MotorPort {
     TwoMotors,
-    SingleStepper,
+    SingleStepper(StepperConfig),
     SingleMotorFirst,
     SingleMotorSecond,
     Empty,
 }
 
-pub struct ShieldLayout {
-    pub port1: MotorPort,
-    pub port2: MotorPort,
+impl MotorPort {
+    // Whether the port drives its first PWM channel (D11 on port 1, D6 on port 2).
+    pub(crate) fn uses_first(&self) -> bool {
+        matches!(self, Self::TwoMotors | Self::SingleStepper(_) | Self::SingleMotorFirst)
+    }
+
+    // Whether the port drives its second PWM channel (D3 on port 1, D5 on port 2).
+    pub(crate) fn uses_second(&self) -> bool {
+        matches!(self, Self::TwoMotors | Self::SingleStepper(_) | Self::SingleMotorSecond)
+    }
+}
+
+// What a motor port drives, either chosen at run time with a `MotorPort` or
+// fixed in the type with one of the markers below.  A shield built from
+// markers only has accessors for the outputs they configure, e.g.
+// `MotorShield::m3` only exists when port 2 carries a first motor.
+pub trait PortLayout {
+    fn port(&self) -> MotorPort;
+}
+
+// Markers for ports whose first (M1/M3), second (M2/M4) motor or stepper is
+// fixed by the layout type.
+pub trait FirstMotor: PortLayout {}
+pub trait SecondMotor: PortLayout {}
+pub trait StepperPort: PortLayout {}
+
+impl PortLayout for MotorPort {
+    fn port(&self) -> MotorPort {
+        *self
+    }
+}
+
+pub struct TwoMotors;
+pub struct SingleStepper(pub StepperConfig);
+pub struct SingleMotorFirst;
+pub struct SingleMotorSecond;
+pub struct Empty;
+
+impl PortLayout for TwoMotors {
+    fn port(&self) -> MotorPort {
+        MotorPort::TwoMotors
+    }
+}
+
+impl PortLayout for SingleStepper {
+    fn port(&self) -> MotorPort {
+        MotorPort::SingleStepper(self.0)
+    }
+}
+
+impl PortLayout for SingleMotorFirst {
+    fn port(&self) -> MotorPort {
+        MotorPort::SingleMotorFirst
+    }
+}
+
+impl PortLayout for SingleMotorSecond {
+    fn port(&self) -> MotorPort {
+        MotorPort::SingleMotorSecond
+    }
+}
+
+impl PortLayout for Empty {
+    fn port(&self) -> MotorPort {
+        MotorPort::Empty
+    }
+}
+
+impl FirstMotor for TwoMotors {}
+impl SecondMotor for TwoMotors {}
+impl FirstMotor for SingleMotorFirst {}
+impl SecondMotor for SingleMotorSecond {}
+impl StepperPort for SingleStepper {}
+
+// PWM frequency of port 1, set on Timer2, which drives both of its channels:
+// every motor or stepper on the port runs at this frequency.  The classic
+// `MOTOR12_64KHZ` ... `MOTOR12_1KHZ` options.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Port1Frequency {
+    Khz64,
+    Khz8,
+    Khz2,
+    #[default]
+    Khz1,
+}
+
+impl Port1Frequency {
+    // Timer2 clock divider for this frequency: 16 MHz / 256 / prescaler.
+    pub const fn prescaler(self) -> u16 {
+        match self {
+            Self::Khz64 => 1,
+            Self::Khz8 => 8,
+            Self::Khz2 => 32,
+            Self::Khz1 => 64,
+        }
+    }
+}
+
+// PWM frequency of port 2, set on Timer0, which drives both of its channels.
+// Timer0 has no /32 prescaler, so unlike port 1 there is no 2 kHz option:
+// the classic `MOTOR34_64KHZ`, `MOTOR34_8KHZ` and `MOTOR34_1KHZ`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Port2Frequency {
+    Khz64,
+    Khz8,
+    #[default]
+    Khz1,
+}
+
+impl Port2Frequency {
+    // Timer0 clock divider for this frequency: 16 MHz / 256 / prescaler.
+    pub const fn prescaler(self) -> u16 {
+        match self {
+            Self::Khz64 => 1,
+            Self::Khz8 => 8,
+            Self::Khz1 => 64,
+        }
+    }
+}
+
+pub struct ShieldLayout<P1 = MotorPort, P2 = MotorPort> {
+    pub port1: P1,
+    pub port2: P2,
+    pub frequency1: Port1Frequency,
+    pub frequency2: Port2Frequency,
+    // Whether a servo is plugged into the SER1 (D10) and SER2 (D9) headers.
+    // Timer1 is only claimed when at least one is.
+    pub servo1: bool,
+    pub servo2: bool,
 }
 
-pub struct Steppers {
-    pub(crate) s1: Option<Stepper>,
-    pub(crate) s2: Option<Stepper>,
+pub struct Steppers<B: Board> {
+    pub(crate) s1: Option<StepperChannel<B::MotorPwm>>,
+    pub(crate) s2: Option<StepperChannel<B::MotorPwm>>,
 }
 
-impl Steppers {
+impl<B: Board> Steppers<B> {
     pub(crate) fn len(&self) -> usize { 2 }
 }
 
-pub struct Servos {
-    pub(crate) s1: Option<Servo>,
-    pub(crate) s2: Option<Servo>,
+pub struct Servos<B: Board> {
+    pub(crate) s1: Option<Servo<B::ServoPwm>>,
+    pub(crate) s2: Option<Servo<B::ServoPwm>>,
 }
 
-impl Servos {
+impl<B: Board> Servos<B> {
     pub fn len(&self) -> usize { 2 }
 }
 
-pub struct Motors {
-    pub m1: Option<Motor>,
-    pub m2: Option<Motor>,
-    pub m3: Option<Motor>,
-    pub m4: Option<Motor>,
+pub struct Motors<B: Board> {
+    pub m1: Option<MotorChannel<B::MotorPwm>>,
+    pub m2: Option<MotorChannel<B::MotorPwm>>,
+    pub m3: Option<MotorChannel<B::MotorPwm>>,
+    pub m4: Option<MotorChannel<B::MotorPwm>>,
 }
 
-impl Motors {
+impl<B: Board> Motors<B> {
     pub fn len(&self) -> usize { 4 }
 }
\ No newline at end of file
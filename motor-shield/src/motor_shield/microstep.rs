@@ -0,0 +1,85 @@
+// Microstep resolutions and the coil current curves they step through.
+
+// Points per quarter cycle the curves are tabulated at: the finest
+// resolution supported.
+pub const CURVE_POINTS: usize = 64;
+
+// Quarter sine wave from 0 to 255 over 0..=90 degrees, worked out at compile
+// time.
+pub const SINE_CURVE: [u8; CURVE_POINTS + 1] = sine_curve();
+
+// Microsteps per full step in MICROSTEP style.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MicrostepResolution {
+    X8,
+    X16,
+    X32,
+    X64,
+}
+
+impl MicrostepResolution {
+    pub const fn microsteps(self) -> u16 {
+        match self {
+            Self::X8 => 8,
+            Self::X16 => 16,
+            Self::X32 => 32,
+            Self::X64 => 64,
+        }
+    }
+}
+
+// Coil duty over a quarter of the electrical cycle: entry `i` is the duty of
+// the rising coil `i / CURVE_POINTS` of the way from one full step to the
+// next, the falling coil taking entry `CURVE_POINTS - i`.
+#[derive(Clone, Copy)]
+pub enum MicrostepCurve {
+    Sine,
+    // A non-sine table, e.g. to compensate for a motor's detent torque.
+    // Should rise from 0 to 255.
+    Custom(&'static [u8; CURVE_POINTS + 1]),
+}
+
+impl MicrostepCurve {
+    // Duty at point `step` of a quarter cycle made of `microsteps` points.
+    pub(crate) fn duty(self, step: u16, microsteps: u16) -> u8 {
+        let index = step as usize * (CURVE_POINTS / microsteps as usize);
+
+        match self {
+            Self::Sine => SINE_CURVE[index],
+            Self::Custom(table) => table[index],
+        }
+    }
+}
+
+// Integer Taylor series, as floats are not usable in const fns: sin(x) in
+// 2^-40 units for x in 2^-40 radians, up to x^15.
+const fn sine_q40(x: i128) -> i128 {
+    let x2 = (x * x) >> 40;
+    let mut term = x;
+    let mut sum = x;
+    let mut k = 1;
+
+    while k < 8 {
+        term = -((term * x2) >> 40) / ((2 * k) * (2 * k + 1));
+        sum += term;
+        k += 1;
+    }
+
+    sum
+}
+
+const fn sine_curve() -> [u8; CURVE_POINTS + 1] {
+    // pi / 2 in 2^-40 units.
+    const HALF_PI: i128 = 1_727_108_826_179;
+    let mut curve = [0; CURVE_POINTS + 1];
+    let mut i = 0;
+
+    while i <= CURVE_POINTS {
+        let sine = sine_q40(HALF_PI * i as i128 / CURVE_POINTS as i128);
+
+        curve[i] = ((sine * 255 + (1 << 39)) >> 40) as u8;
+        i += 1;
+    }
+
+    curve
+}
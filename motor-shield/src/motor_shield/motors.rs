@@ -1,138 +1,243 @@
-use arduino_hal::{
-    hal::port,
-    port::{mode, Pin},
-    simple_pwm::{Timer0Pwm,Timer2Pwm}
-};
-
-use super::digital_output::DigitalOutput;
+use super::{digital_output::{DigitalOutput, ShiftOut}, pwm::PwmChannel};
 
+// Largest duty magnitude accepted by `Motor::set_velocity`.
+pub const MAX_VELOCITY: i16 = u8::MAX as i16;
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum MotorCommands {
     FORWARD,
     BACKWARD,
     RELEASE,
+    // Both bridge inputs high: the L293D shorts the motor terminals.
+    BRAKE,
 }
-pub enum MotorPin {
-    Motor1(Pin<mode::PwmOutput<Timer2Pwm>, port::PB3>),
-    Motor2(Pin<mode::PwmOutput<Timer2Pwm>, port::PD3>),
-    Motor3(Pin<mode::PwmOutput<Timer0Pwm>, port::PD6>),
-    Motor4(Pin<mode::PwmOutput<Timer0Pwm>, port::PD5>),
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StopMode {
+    // Release the bridge and let the motor spin down.
+    Coast,
+    // Short the motor through the bridge at full duty.
+    Brake,
 }
 
-impl MotorPin {
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MotorId {
+    M1,
+    M2,
+    M3,
+    M4,
+}
+
+impl MotorId {
+    pub const ALL: [MotorId; 4] = [Self::M1, Self::M2, Self::M3, Self::M4];
+
     fn get_ab(&self) -> (u8, u8) {
         match self {
             // #define MOTOR1_A 2 0x0000_0100
             // #define MOTOR1_B 3 0x0000_1000
-            Self::Motor1(_) => (1 << 2, 1 << 3),
+            Self::M1 => (1 << 2, 1 << 3),
             // #define MOTOR2_A 1 0x0000_0010
             // #define MOTOR2_B 4 0x0001_0000
-            Self::Motor2(_) => (1 << 1, 1 << 4),
+            Self::M2 => (1 << 1, 1 << 4),
             // #define MOTOR3_A 5 0x0010_0000
             // #define MOTOR3_B 7 0x1000_0000
-            Self::Motor3(_) => (1 << 5, 1 << 7),
+            Self::M3 => (1 << 5, 1 << 7),
             // #define MOTOR4_A 0 0x0000_0001
             // #define MOTOR4_B 6 0x0100_0000
-            Self::Motor4(_) => (1 << 0, 1 << 6),
+            Self::M4 => (1 << 0, 1 << 6),
         }
     }
+}
 
-    fn enable(&mut self) {
-        match self {
-            Self::Motor1(pin) => {
-                pin.enable();
-            }
-            Self::Motor2(pin) => {
-                pin.enable();
-            }
-            Self::Motor3(pin) => {
-                pin.enable();
-            }
-            Self::Motor4(pin) => {
-                pin.enable();
-            }
-        }
-    }
+// Per-motor state kept by the shield between calls.
+pub struct MotorChannel<P> {
+    pin: P,
+    id: MotorId,
+    // Last command latched and duty written, for `Motor::velocity`.
+    command: MotorCommands,
+    duty: u8,
+    // Velocity `MotorShield::update` ramps towards, and the largest duty
+    // change it makes per tick; without a limit velocities apply at once.
+    target: i16,
+    acceleration: Option<u16>,
+}
 
-    fn disable(&mut self) {
-        match self {
-            Self::Motor1(pin) => {
-                pin.disable();
-            }
-            Self::Motor2(pin) => {
-                pin.disable();
-            }
-            Self::Motor3(pin) => {
-                pin.disable();
-            }
-            Self::Motor4(pin) => {
-                pin.disable();
-            }
+impl<P: PwmChannel> MotorChannel<P> {
+    pub(crate) fn new(pin: P, id: MotorId) -> Self {
+        Self {
+            pin,
+            id,
+            command: MotorCommands::RELEASE,
+            duty: 0,
+            target: 0,
+            acceleration: None,
         }
     }
 
-    fn set_speed(&mut self, speed: u8) {
-        match self {
-            Self::Motor1(pin) => {
-                pin.set_duty(speed);
-            }
-            Self::Motor2(pin) => {
-                pin.set_duty(speed);
-            }
-            Self::Motor3(pin) => {
-                pin.set_duty(speed);
-            }
-            Self::Motor4(pin) => {
-                pin.set_duty(speed);
-            }
-        }
+    pub(crate) fn into_pin(self) -> P {
+        self.pin
     }
 }
 
-pub struct Motor {
-    pin: MotorPin,
-    output: *mut DigitalOutput,
+// A DC motor borrowed from the shield together with the shared latch.
+pub struct Motor<'a, P, T> {
+    channel: &'a mut MotorChannel<P>,
+    output: &'a mut DigitalOutput<T>,
 }
 
-impl Motor {
-    pub fn new(pin: MotorPin, output: *mut DigitalOutput,) -> Self {
+impl<'a, P: PwmChannel, T: ShiftOut> Motor<'a, P, T> {
+    pub(crate) fn new(channel: &'a mut MotorChannel<P>, output: &'a mut DigitalOutput<T>) -> Self {
         Self {
-            pin,
+            channel,
             output
         }
     }
 
-    pub fn run(&self, command: MotorCommands) {
-        let (a, b) = self.pin.get_ab();
-        let output = unsafe { self.output.as_mut().unwrap()};
+    pub fn run(&mut self, command: MotorCommands) {
+        self.latch_command(command);
+        self.channel.target = self.velocity();
+    }
+
+    fn latch_command(&mut self, command: MotorCommands) {
+        let (a, b) = self.channel.id.get_ab();
 
         match command {
             MotorCommands::FORWARD => {
-                output.or(a);
-                output.and_not(b);
+                self.output.or(a);
+                self.output.and_not(b);
             }
             MotorCommands::BACKWARD => {
-                output.and_not(a);
-                output.or(b);
+                self.output.and_not(a);
+                self.output.or(b);
             }
             MotorCommands::RELEASE => {
-                output.and_not(a);
-                output.and_not(b);
+                self.output.and_not(a);
+                self.output.and_not(b);
+            }
+            MotorCommands::BRAKE => {
+                self.output.or(a);
+                self.output.or(b);
             }
         }
 
-        output.transmit();
+        self.output.transmit();
+        self.channel.command = command;
+    }
+
+    // Drives the motor at `velocity` in -255..=255, positive being FORWARD;
+    // zero releases it.  Out-of-range values are clamped.  On a reversal the
+    // duty drops to zero before the direction bits change.  With an
+    // acceleration limit this only sets the target for `MotorShield::update`.
+    pub fn set_velocity(&mut self, velocity: i16) {
+        self.set_target(velocity);
+
+        if self.channel.acceleration.is_none() {
+            self.drive(self.channel.target);
+        }
+    }
+
+    // Limits how far `MotorShield::update` moves the duty per tick; `None`
+    // lets velocities apply at once.  `Some(0)`, which would never move, is
+    // taken as `None`.
+    pub fn set_acceleration(&mut self, acceleration: Option<u16>) {
+        self.channel.acceleration = acceleration.filter(|&acceleration| acceleration != 0);
+    }
+
+    pub fn acceleration(&self) -> Option<u16> {
+        self.channel.acceleration
+    }
+
+    // Velocity last requested through `set_velocity`, or the one applied by
+    // `run`, `speed` or `stop`.
+    pub fn target_velocity(&self) -> i16 {
+        self.channel.target
+    }
+
+    // Signed velocity last applied, from the latched direction and duty.
+    pub fn velocity(&self) -> i16 {
+        match self.channel.command {
+            MotorCommands::FORWARD => self.channel.duty as i16,
+            MotorCommands::BACKWARD => -(self.channel.duty as i16),
+            MotorCommands::RELEASE | MotorCommands::BRAKE => 0,
+        }
+    }
+
+    // Coasting drops the duty before releasing the bridge; braking sets both
+    // bridge inputs before raising the duty, so the motor is never driven
+    // harder in its old direction.
+    pub fn stop(&mut self, mode: StopMode) {
+        match mode {
+            StopMode::Coast => {
+                self.write_duty(0);
+                self.latch_command(MotorCommands::RELEASE);
+            }
+            StopMode::Brake => {
+                self.latch_command(MotorCommands::BRAKE);
+                self.write_duty(u8::MAX);
+            }
+        }
+
+        self.channel.target = 0;
+    }
+
+    pub(crate) fn set_target(&mut self, velocity: i16) {
+        self.channel.target = velocity.clamp(-MAX_VELOCITY, MAX_VELOCITY);
+    }
+
+    // Velocity to apply after `ticks` more ticks of ramping towards the
+    // target.  A reversal stops at zero first, so the direction only ever
+    // flips from standstill.
+    pub(crate) fn next_velocity(&self, ticks: u16) -> i16 {
+        let (current, target) = (self.velocity(), self.channel.target);
+
+        let Some(acceleration) = self.channel.acceleration else {
+            return target;
+        };
+
+        let goal = if current.signum() * target.signum() < 0 { 0 } else { target };
+        let step = (acceleration as u32 * ticks as u32).min(2 * MAX_VELOCITY as u32) as i16;
+
+        current + (goal - current).clamp(-step, step)
+    }
+
+    // Applies `velocity` right away, leaving the target alone.
+    pub(crate) fn drive(&mut self, velocity: i16) {
+        self.set_direction(velocity);
+        self.write_duty(velocity.unsigned_abs() as u8);
+    }
+
+    // Direction half of `set_velocity`, split out so several motors can latch
+    // their bits together before any new duty is applied.
+    pub(crate) fn set_direction(&mut self, velocity: i16) {
+        let command = match velocity.signum() {
+            1 => MotorCommands::FORWARD,
+            -1 => MotorCommands::BACKWARD,
+            _ => MotorCommands::RELEASE,
+        };
+
+        if command != self.channel.command {
+            if self.channel.duty != 0 {
+                self.write_duty(0);
+            }
+            self.latch_command(command);
+        }
     }
 
     pub fn enable(&mut self) {
-        self.pin.enable()
+        self.channel.pin.enable()
     }
 
     pub fn disable(&mut self) {
-        self.pin.disable();
+        self.channel.pin.disable();
     }
 
     pub fn speed(&mut self, speed: u8) {
-        self.pin.set_speed(speed);
+        self.write_duty(speed);
+        self.channel.target = self.velocity();
+    }
+
+    fn write_duty(&mut self, speed: u8) {
+        let _ = self.channel.pin.set_duty_cycle_fraction(speed as u16, u8::MAX as u16);
+        self.channel.duty = speed;
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,89 @@
+use arduino_hal::{
+    hal::port,
+    port::{mode, Pin},
+};
+
+use super::digital_output::ShiftOut;
+
+// Unrolled bit-bang on the concrete port pins, selected by the `fast-shift`
+// feature.  Every clock edge is a single `sbi`/`cbi` on PORTD and each data
+// bit is tested against a constant mask.
+//
+// Cycles per latch update at opt-level = "s" (16 MHz), counted from the
+// emitted assembly:
+//
+//   DigitalOutput::transmit before the split, and BitBang
+//              392 cycles, 24.5 us  (variable `1 << (7 - i)` shift per bit)
+//   PortShift   96 cycles,  6.0 us
+//
+// `examples/shift_cycles.rs` measures all three on the board with Timer1.
+//
+// The USART in SPI master mode is not an option on this shield: it shifts
+// data out on D1 (TXD) rather than D8, and the hardware SPI clock sits on
+// D13 with MOSI on D11, which is the motor 1 PWM.
+pub struct PortShift {
+    clock: Pin<mode::Output, port::PD4>,
+    latch: Pin<mode::Output, port::PB4>,
+    data: Pin<mode::Output, port::PB0>,
+    enable: Pin<mode::Output, port::PD7>,
+}
+
+impl PortShift {
+    pub fn new(
+        clock: Pin<mode::Output, port::PD4>,
+        latch: Pin<mode::Output, port::PB4>,
+        data: Pin<mode::Output, port::PB0>,
+        enable: Pin<mode::Output, port::PD7>,
+    ) -> Self {
+        Self {
+            clock,
+            latch,
+            data,
+            enable,
+        }
+    }
+
+    // Hands back the clock, latch, data and enable pins.
+    pub fn release(
+        self,
+    ) -> (
+        Pin<mode::Output, port::PD4>,
+        Pin<mode::Output, port::PB4>,
+        Pin<mode::Output, port::PB0>,
+        Pin<mode::Output, port::PD7>,
+    ) {
+        (self.clock, self.latch, self.data, self.enable)
+    }
+
+    #[inline(always)]
+    fn bit(&mut self, byte: u8, mask: u8) {
+        self.clock.set_low();
+        if byte & mask != 0 {
+            self.data.set_high();
+        } else {
+            self.data.set_low();
+        }
+        self.clock.set_high();
+    }
+}
+
+impl ShiftOut for PortShift {
+    fn shift_out(&mut self, byte: u8) {
+        self.latch.set_low();
+
+        self.bit(byte, 1 << 7);
+        self.bit(byte, 1 << 6);
+        self.bit(byte, 1 << 5);
+        self.bit(byte, 1 << 4);
+        self.bit(byte, 1 << 3);
+        self.bit(byte, 1 << 2);
+        self.bit(byte, 1 << 1);
+        self.bit(byte, 1 << 0);
+
+        self.latch.set_high();
+    }
+
+    fn enable_outputs(&mut self) {
+        self.enable.set_low();
+    }
+}
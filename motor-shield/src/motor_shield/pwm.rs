@@ -0,0 +1,7 @@
+use embedded_hal::pwm::SetDutyCycle;
+
+// A PWM output that can be connected to and disconnected from its timer.
+pub trait PwmChannel: SetDutyCycle {
+    fn enable(&mut self);
+    fn disable(&mut self);
+}
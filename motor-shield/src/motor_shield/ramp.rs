@@ -0,0 +1,101 @@
+// Integer form of the AccelStepper speed ramp (D. Austin, "Generate stepper
+// motor speed profiles in real time").  Intervals are kept in 1/256 us so the
+// per-step correction does not round away once the motor is fast.
+
+// (0.676 * 1e6 * 256)^2 * 2: the square of the first interval, in 1/256 us,
+// times the acceleration in steps/s^2.
+const C0_SQUARED: u64 = 913_952_000_000 * 65_536;
+const SECOND: u64 = 256_000_000;
+
+#[derive(Clone, Copy)]
+pub(crate) struct Ramp {
+    // Ramp step counter: positive while speeding up or cruising, negative
+    // while slowing down.  From standstill it is also the distance covered
+    // so far, v^2 / 2a, so it gives the steps needed to stop.
+    n: i32,
+    // Value of `n` once the shortest interval is reached: the steps it takes
+    // to stop from cruising speed.
+    cruise: i32,
+    // Current, first and shortest interval, in 1/256 us.
+    c: u32,
+    c0: u32,
+    cmin: u32,
+}
+
+impl Ramp {
+    // Works out the first interval and the cruise distance, the only 64-bit
+    // math; every step after that stays in 32 bits.
+    pub(crate) fn new(acceleration: u32, min_interval_us: u32) -> Self {
+        let acceleration = acceleration.max(1) as u64;
+        let c0 = isqrt(C0_SQUARED / acceleration) as u32;
+        let cmin = min_interval_us.saturating_mul(256);
+        let speed = SECOND / cmin.max(1) as u64;
+
+        Self {
+            n: 0,
+            cruise: (speed * speed / (2 * acceleration)).min(i32::MAX as u64) as i32,
+            c: c0,
+            c0,
+            cmin,
+        }
+    }
+
+    // Back to standstill, as at the start of a move.
+    pub(crate) fn restart(&mut self) {
+        self.n = 0;
+        self.c = self.c0;
+    }
+
+    // Steps needed to stop from the current speed: the ramp counter, capped
+    // at the cruise distance.  While slowing down that is the counter itself,
+    // which keeps rounding from flipping the ramp back to speeding up.
+    pub(crate) fn steps_to_stop(&self) -> u32 {
+        self.n.min(self.cruise).unsigned_abs()
+    }
+
+    // Interval in us until the next step, with `remaining` steps left after
+    // the one just taken.  Starts slowing down once the remaining distance
+    // is what it takes to stop.
+    pub(crate) fn next_interval(&mut self, remaining: u32) -> u32 {
+        let to_stop = self.steps_to_stop();
+
+        if self.n > 0 && to_stop >= remaining {
+            self.n = -(to_stop as i32);
+        } else if self.n < 0 && to_stop < remaining {
+            self.n = -self.n;
+        }
+
+        self.c = if self.n == 0 {
+            self.c0
+        } else {
+            let c = self.c as i32;
+
+            (c - 2 * c / self.n.saturating_mul(4).saturating_add(1)) as u32
+        }
+        .max(self.cmin);
+        self.n = self.n.saturating_add(1);
+
+        self.c >> 8
+    }
+}
+
+fn isqrt(mut x: u64) -> u64 {
+    let mut root = 0;
+    let mut bit = 1 << 62;
+
+    while bit > x {
+        bit >>= 2;
+    }
+
+    while bit != 0 {
+        if x >= root + bit {
+            x -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+
+    root
+}
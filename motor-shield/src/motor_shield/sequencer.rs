@@ -0,0 +1,111 @@
+use super::{board::Board, layout::PortLayout, servos::{Easing, ServoId}, MotorShield};
+
+// One pose of a servo sequence.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Keyframe {
+    // Targets for S1 and S2 in degrees.  `None` leaves that servo where it
+    // is, so a frame with neither is a pause.
+    pub degrees: [Option<u8>; 2],
+    pub duration_ms: u16,
+    pub easing: Easing,
+}
+
+impl Keyframe {
+    pub const fn new(s1: Option<u8>, s2: Option<u8>, duration_ms: u16, easing: Easing) -> Self {
+        Keyframe { degrees: [s1, s2], duration_ms, easing }
+    }
+}
+
+// Plays keyframes on the shield servos.  Both servos start each frame
+// together and take its whole duration, so they arrive together.
+pub struct ServoSequence<'a> {
+    frames: &'a [Keyframe],
+    looping: bool,
+    // Frame being played, `None` before the first update and `frames.len()`
+    // once finished.
+    frame: Option<usize>,
+    elapsed_ms: u16,
+}
+
+impl<'a> ServoSequence<'a> {
+    pub const fn new(frames: &'a [Keyframe]) -> Self {
+        ServoSequence { frames, looping: false, frame: None, elapsed_ms: 0 }
+    }
+
+    // Whether the sequence starts over after its last frame.
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    // Plays again from the first frame on the next update.
+    pub fn restart(&mut self) {
+        self.frame = None;
+        self.elapsed_ms = 0;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame.is_some_and(|frame| frame >= self.frames.len())
+    }
+
+    // Advances the sequence by `dt_ms`, starting it on the first call, and
+    // moves the servos of `shield` along.  Servos the layout leaves out are
+    // skipped.  Returns whether the sequence is still playing.
+    pub fn update<B: Board, P1: PortLayout, P2: PortLayout>(
+        &mut self,
+        shield: &mut MotorShield<B, P1, P2>,
+        dt_ms: u16,
+    ) -> bool {
+        let mut index = match self.frame {
+            Some(index) => index,
+            None => self.start_frame(shield, 0),
+        };
+
+        let mut left = dt_ms;
+
+        // Bounded so a looping sequence of zero length frames cannot spin.
+        for _ in 0..=self.frames.len() {
+            let Some(frame) = self.frames.get(index) else {
+                return false;
+            };
+
+            let step = left.min(frame.duration_ms - self.elapsed_ms);
+
+            self.elapsed_ms += step;
+            left -= step;
+            shield.update_servos(step);
+
+            if self.elapsed_ms < frame.duration_ms {
+                return true;
+            }
+
+            index += 1;
+
+            if index == self.frames.len() && self.looping {
+                index = 0;
+            }
+
+            index = self.start_frame(shield, index);
+        }
+
+        true
+    }
+
+    fn start_frame<B: Board, P1: PortLayout, P2: PortLayout>(
+        &mut self,
+        shield: &mut MotorShield<B, P1, P2>,
+        index: usize,
+    ) -> usize {
+        self.frame = Some(index);
+        self.elapsed_ms = 0;
+
+        if let Some(frame) = self.frames.get(index) {
+            for (id, degrees) in ServoId::ALL.into_iter().zip(frame.degrees) {
+                if let (Some(degrees), Some(servo)) = (degrees, shield.servo(id)) {
+                    servo.start_move(servo.degrees_pulse(degrees), frame.duration_ms, frame.easing);
+                }
+            }
+        }
+
+        index
+    }
+}
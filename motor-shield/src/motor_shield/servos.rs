@@ -1,56 +1,86 @@
-use arduino_hal::{
-    hal::port,
-    port::{mode, Pin},
-    simple_pwm::Timer1Pwm
-};
-
-pub enum ServoPin {
-    Servo1(Pin<mode::PwmOutput<Timer1Pwm>, port::PB2>),
-    Servo2(Pin<mode::PwmOutput<Timer1Pwm>, port::PB1>),
+use super::pwm::PwmChannel;
+
+// Length of one servo frame: the PWM period the servo channels run at.
+pub const SERVO_PERIOD_US: u16 = 20_000;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ServoId {
+    S1,
+    S2,
 }
 
-impl ServoPin {
-    pub fn enable(&mut self) {
-        match self {
-            Self::Servo1(pin) => {
-                pin.enable();
-            }
-            Self::Servo2(pin) => {
-                pin.enable();
-            }
-        }
-    }
+impl ServoId {
+    pub const ALL: [ServoId; 2] = [Self::S1, Self::S2];
+}
+
+// Speed profile of a timed servo move.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Easing {
+    // Constant speed.
+    Linear,
+    // Smoothstep: starts and ends at rest, fastest half way.
+    EaseInOut,
+}
+
+impl Easing {
+    // Progress after `elapsed` out of `duration`, with 1 << 16 for done.
+    fn progress(self, elapsed: u16, duration: u16) -> u32 {
+        let t = ((elapsed.min(duration) as u64) << 16) / duration.max(1) as u64;
 
-    pub fn disable(&mut self) {
         match self {
-            Self::Servo1(pin) => {
-                pin.disable();
-            }
-            Self::Servo2(pin) => {
-                pin.disable();
-            }
+            Easing::Linear => t as u32,
+            Easing::EaseInOut => ((t * t * (3 * (1 << 16) - 2 * t)) >> 32) as u32,
         }
     }
+}
 
-    pub fn set_angle(&mut self, angle: u8) {
-        match self {
-            Self::Servo1(pin) => {
-                pin.set_duty(angle);
-            }
-            Self::Servo2(pin) => {
-                pin.set_duty(angle);
-            }
-        }
+// A timed move between two pulse widths.
+#[derive(Debug, Clone, Copy)]
+struct Sweep {
+    from_us: u16,
+    to_us: u16,
+    duration_ms: u16,
+    elapsed_ms: u16,
+    easing: Easing,
+}
+
+impl Sweep {
+    fn pulse_us(&self) -> u16 {
+        let progress = self.easing.progress(self.elapsed_ms, self.duration_ms) as i32;
+        let (from, to) = (self.from_us as i32, self.to_us as i32);
+
+        (from + (((to - from) * progress) >> 16)) as u16
     }
 }
 
-pub struct Servo {
-    pin: ServoPin,
+pub struct Servo<P> {
+    pin: P,
+    // Pulse widths at 0 and 180 degrees.
+    min_us: u16,
+    max_us: u16,
+    easing: Easing,
+    // Last pulse width written, unknown until the first one.
+    pulse_us: Option<u16>,
+    sweep: Option<Sweep>,
 }
 
-impl Servo {
-    pub fn new(pin: ServoPin) -> Self {
-        Servo { pin }
+impl<P: PwmChannel> Servo<P> {
+    pub const MIN_PULSE_US: u16 = 1_000;
+    pub const MAX_PULSE_US: u16 = 2_000;
+
+    pub fn new(pin: P) -> Self {
+        Servo {
+            pin,
+            min_us: Self::MIN_PULSE_US,
+            max_us: Self::MAX_PULSE_US,
+            easing: Easing::Linear,
+            pulse_us: None,
+            sweep: None,
+        }
+    }
+
+    pub(crate) fn into_pin(self) -> P {
+        self.pin
     }
 
     pub fn enable(&mut self) {
@@ -61,7 +91,103 @@ impl Servo {
         self.pin.disable();
     }
 
+    // Sets the pulse widths `set_degrees` maps 0 and 180 degrees to, for
+    // servos whose travel does not match the nominal 1-2 ms.  `min_us` may be
+    // above `max_us` to reverse the servo.
+    pub fn set_calibration(&mut self, min_us: u16, max_us: u16) {
+        self.min_us = min_us.min(SERVO_PERIOD_US);
+        self.max_us = max_us.min(SERVO_PERIOD_US);
+    }
+
+    pub fn calibration(&self) -> (u16, u16) {
+        (self.min_us, self.max_us)
+    }
+
+    pub fn easing(&self) -> Easing {
+        self.easing
+    }
+
+    // Sets the easing used by `move_to`.
+    pub fn set_easing(&mut self, easing: Easing) {
+        self.easing = easing;
+    }
+
+    // Sets the high time of every frame, capped at the frame length.  Cancels
+    // any move in progress.
+    pub fn set_pulse_us(&mut self, pulse_us: u16) {
+        self.sweep = None;
+        self.write_pulse(pulse_us);
+    }
+
+    // The last pulse width written, if any.
+    pub fn pulse_us(&self) -> Option<u16> {
+        self.pulse_us
+    }
+
+    // Moves to `degrees`, capped at 180, within the calibrated pulse range.
+    pub fn set_degrees(&mut self, degrees: u8) {
+        self.set_pulse_us(self.degrees_pulse(degrees));
+    }
+
+    // Moves to `angle` out of 255 across the calibrated pulse range.
     pub fn set_angle(&mut self, angle: u8) {
-        self.pin.set_angle(angle);
+        self.set_pulse_us(self.pulse_at(angle as u16, u8::MAX as u16));
     }
-}
\ No newline at end of file
+
+    // Starts moving to `degrees`, capped at 180, over `duration_ms`, eased as
+    // set by `set_easing`.  The move is advanced by `update`.  A servo that
+    // has not been positioned yet jumps straight there.
+    pub fn move_to(&mut self, degrees: u8, duration_ms: u16) {
+        self.start_move(self.degrees_pulse(degrees), duration_ms, self.easing);
+    }
+
+    pub(crate) fn start_move(&mut self, to_us: u16, duration_ms: u16, easing: Easing) {
+        match self.pulse_us {
+            Some(from_us) if duration_ms > 0 => {
+                self.sweep = Some(Sweep { from_us, to_us, duration_ms, elapsed_ms: 0, easing });
+            },
+            _ => self.set_pulse_us(to_us),
+        }
+    }
+
+    // Advances a move started by `move_to` by `dt_ms`.  Returns whether it is
+    // still in progress.
+    pub fn update(&mut self, dt_ms: u16) -> bool {
+        let Some(mut sweep) = self.sweep else {
+            return false;
+        };
+
+        sweep.elapsed_ms = sweep.elapsed_ms.saturating_add(dt_ms).min(sweep.duration_ms);
+        self.write_pulse(sweep.pulse_us());
+
+        let moving = sweep.elapsed_ms < sweep.duration_ms;
+        self.sweep = Some(sweep).filter(|_| moving);
+
+        moving
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.sweep.is_some()
+    }
+
+    // Writes `pulse_us` unless it is already the current pulse width.
+    fn write_pulse(&mut self, pulse_us: u16) {
+        let pulse_us = pulse_us.min(SERVO_PERIOD_US);
+
+        if self.pulse_us != Some(pulse_us) {
+            let _ = self.pin.set_duty_cycle_fraction(pulse_us, SERVO_PERIOD_US);
+            self.pulse_us = Some(pulse_us);
+        }
+    }
+
+    // Pulse width for `degrees`, capped at 180.
+    pub(crate) fn degrees_pulse(&self, degrees: u8) -> u16 {
+        self.pulse_at(degrees.min(180) as u16, 180)
+    }
+
+    fn pulse_at(&self, num: u16, denom: u16) -> u16 {
+        let (min, max) = (self.min_us as i32, self.max_us as i32);
+
+        (min + (max - min) * num as i32 / denom as i32) as u16
+    }
+}
@@ -0,0 +1,202 @@
+use core::{cell::RefCell, convert::Infallible};
+use std::{rc::Rc, vec::Vec};
+
+use embedded_hal::pwm::{ErrorType, SetDutyCycle};
+
+use super::{board::Board, digital_output::ShiftOut, layout::{PortLayout, ShieldLayout}, pwm::PwmChannel, MotorShield};
+
+// Recording backend for host tests: every latch, duty change and
+// enable/disable ends up in a shared `Trace`.
+pub struct SimBoard;
+
+impl Board for SimBoard {
+    type MotorPwm = SimPwm;
+    type ServoPwm = SimPwm;
+    type Shift = SimShift;
+    type Timers = ();
+}
+
+pub type SimShield = MotorShield<SimBoard>;
+
+// The shield's PWM outputs, named after the Uno pins they sit on.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SimChannel {
+    D11,
+    D3,
+    D6,
+    D5,
+    D10,
+    D9,
+}
+
+impl SimChannel {
+    // Top of the timer behind the channel, as on the Uno: 8-bit for the
+    // motors, Timer1's 20 ms frame for the servos.
+    pub fn max_duty(self) -> u16 {
+        match self {
+            Self::D10 | Self::D9 => 39_999,
+            _ => u8::MAX as u16,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SimEvent {
+    // A byte latched onto the 74HC595 outputs.
+    Latch(u8),
+    // The 74HC595 output enable was asserted.
+    OutputsEnabled,
+    Duty(SimChannel, u16),
+    Enable(SimChannel),
+    Disable(SimChannel),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SimRecord {
+    // Logical timestamp: position of the event in the trace.
+    pub at: u32,
+    pub event: SimEvent,
+}
+
+#[derive(Default)]
+struct TraceLog {
+    clock: u32,
+    records: Vec<SimRecord>,
+}
+
+// Shared event log; clones refer to the same log.
+#[derive(Clone, Default)]
+pub struct Trace(Rc<RefCell<TraceLog>>);
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(&self, event: SimEvent) {
+        let mut log = self.0.borrow_mut();
+        let at = log.clock;
+
+        log.clock += 1;
+        log.records.push(SimRecord { at, event });
+    }
+
+    pub fn records(&self) -> Vec<SimRecord> {
+        self.0.borrow().records.clone()
+    }
+
+    pub fn events(&self) -> Vec<SimEvent> {
+        self.0.borrow().records.iter().map(|r| r.event).collect()
+    }
+
+    // Every byte latched so far, in order.
+    pub fn latched(&self) -> Vec<u8> {
+        self.events()
+            .into_iter()
+            .filter_map(|e| match e {
+                SimEvent::Latch(byte) => Some(byte),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Every duty written to `channel` so far, in order.
+    pub fn duties(&self, channel: SimChannel) -> Vec<u16> {
+        self.events()
+            .into_iter()
+            .filter_map(|e| match e {
+                SimEvent::Duty(c, duty) if c == channel => Some(duty),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Forgets the recorded events; the logical clock keeps running.
+    pub fn clear(&self) {
+        self.0.borrow_mut().records.clear();
+    }
+}
+
+pub struct SimPwm {
+    channel: SimChannel,
+    trace: Trace,
+}
+
+impl SimPwm {
+    pub fn new(channel: SimChannel, trace: &Trace) -> Self {
+        Self {
+            channel,
+            trace: trace.clone(),
+        }
+    }
+}
+
+impl ErrorType for SimPwm {
+    type Error = Infallible;
+}
+
+impl SetDutyCycle for SimPwm {
+    fn max_duty_cycle(&self) -> u16 {
+        self.channel.max_duty()
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        self.trace.record(SimEvent::Duty(self.channel, duty));
+        Ok(())
+    }
+}
+
+impl PwmChannel for SimPwm {
+    fn enable(&mut self) {
+        self.trace.record(SimEvent::Enable(self.channel));
+    }
+
+    fn disable(&mut self) {
+        self.trace.record(SimEvent::Disable(self.channel));
+    }
+}
+
+pub struct SimShift {
+    trace: Trace,
+}
+
+impl SimShift {
+    pub fn new(trace: &Trace) -> Self {
+        Self {
+            trace: trace.clone(),
+        }
+    }
+}
+
+impl ShiftOut for SimShift {
+    fn shift_out(&mut self, byte: u8) {
+        self.trace.record(SimEvent::Latch(byte));
+    }
+
+    fn enable_outputs(&mut self) {
+        self.trace.record(SimEvent::OutputsEnabled);
+    }
+}
+
+impl<P1: PortLayout, P2: PortLayout> MotorShield<SimBoard, P1, P2> {
+    // A shield with every output wired to `trace`.
+    pub fn simulated(layout: ShieldLayout<P1, P2>, trace: &Trace) -> Self {
+        let (servo1, servo2) = (layout.servo1, layout.servo2);
+
+        Self::build(
+            layout,
+            SimShift::new(trace),
+            (),
+            [
+                Some(SimPwm::new(SimChannel::D11, trace)),
+                Some(SimPwm::new(SimChannel::D3, trace)),
+                Some(SimPwm::new(SimChannel::D6, trace)),
+                Some(SimPwm::new(SimChannel::D5, trace)),
+            ],
+            [
+                Some(SimPwm::new(SimChannel::D10, trace)).filter(|_| servo1),
+                Some(SimPwm::new(SimChannel::D9, trace)).filter(|_| servo2),
+            ],
+        )
+    }
+}
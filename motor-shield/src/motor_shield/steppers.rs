@@ -1,14 +1,13 @@
-use arduino_hal::{
-    hal::port,
-    port::{mode, Pin},
-    simple_pwm::{Timer0Pwm, Timer2Pwm}, delay_ms
+use embedded_hal::{delay::DelayNs, digital::{InputPin, PinState}};
+
+use super::{
+    digital_output::{DigitalOutput, ShiftOut},
+    error::HomingError,
+    microstep::{MicrostepCurve, MicrostepResolution},
+    pwm::PwmChannel,
+    ramp::Ramp,
 };
 
-use super::digital_output::DigitalOutput;
-
-const MICROSTEPS: u8 = 16;
-const MICROSTEP_CURVE: [u8; 17] = [0, 25, 50, 74, 98, 120, 141, 162, 180, 197, 212, 225, 236, 244, 250, 253, 255];
-
 #[derive(PartialEq, Clone, Copy)]
 pub enum StepperStyle {
     SINGLE,
@@ -17,35 +16,70 @@ pub enum StepperStyle {
     MICROSTEP
 }
 
+impl StepperStyle {
+    // `onestep` calls per full step worth of time, with `microsteps` per
+    // full step.
+    pub(crate) fn rate(self, microsteps: u16) -> u32 {
+        match self {
+            Self::INTERLEAVE => 2,
+            Self::MICROSTEP => microsteps as u32,
+            _ => 1,
+        }
+    }
+
+    // `onestep` calls making up `steps` steps, counted as `Stepper::step`
+    // does: INTERLEAVE steps are half steps.  Saturates at `u32::MAX`.
+    pub(crate) fn onesteps(self, steps: u32, microsteps: u16) -> u32 {
+        match self {
+            Self::MICROSTEP => steps.saturating_mul(microsteps as u32),
+            _ => steps,
+        }
+    }
+
+    // Microsteps moved by a regular `onestep` in this style.
+    fn granularity(self, microsteps: u16) -> i32 {
+        microsteps as i32 / self.rate(microsteps) as i32
+    }
+}
+
 #[derive(PartialEq, Clone, Copy)]
 pub enum StepperDirection {
     FORWARD,
     BACKWARD,
 }
 
-pub enum StepperPin {
-    Stepper1(
-        (
-            Pin<mode::PwmOutput<Timer2Pwm>, port::PB3>,
-            Pin<mode::PwmOutput<Timer2Pwm>, port::PD3>,
-        ),
-    ),
-    Stepper2(
-        (
-            Pin<mode::PwmOutput<Timer0Pwm>, port::PD6>,
-            Pin<mode::PwmOutput<Timer0Pwm>, port::PD5>,
-        ),
-    ),
+impl StepperDirection {
+    fn sign(self) -> i32 {
+        match self {
+            Self::FORWARD => 1,
+            Self::BACKWARD => -1,
+        }
+    }
+
+    fn reversed(self) -> Self {
+        match self {
+            Self::FORWARD => Self::BACKWARD,
+            Self::BACKWARD => Self::FORWARD,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StepperId {
+    S1,
+    S2,
 }
 
-impl StepperPin {
+impl StepperId {
+    pub const ALL: [StepperId; 2] = [Self::S1, Self::S2];
+
     fn get_abcd(&self) -> (u8, u8, u8, u8) {
         match self {
             // STEPPER1_A 2 0x0000_0200
             // STEPPER1_C 1 0x0000_0010
             // STEPPER1_B 3 0x0000_1000
             // STEPPER1_D 4 0x0001_0000
-            Self::Stepper1(_) => (
+            Self::S1 => (
                 1 << 2,
                 1 << 1,
                 1 << 3,
@@ -54,242 +88,693 @@ impl StepperPin {
             // STEPPER2_C 0 0x0000_0001
             // STEPPER2_B 7 0x1000_0000
             // STEPPER2_D 6 0x0100_0000
-            Self::Stepper2(_) => (
+            Self::S2 => (
                 1 << 5,
                 1 << 0,
                 1 << 7,
                 1 << 6),
         }
     }
+}
 
-    pub fn set_dutys(&mut self, duty1: u8, duty2: u8) {
-        match self {
-            Self::Stepper1((pin1, pin2)) => {
-                pin1.set_duty(duty1);
-                pin2.set_duty(duty2);
-            }
-            Self::Stepper2((pin1, pin2)) => {
-                pin1.set_duty(duty1);
-                pin2.set_duty(duty2);
-            }
-        }
-    }
-    pub fn enable(&mut self) {
-        match self {
-            Self::Stepper1((pin1, pin2)) => {
-                pin1.enable();
-                pin2.enable();
-            }
-            Self::Stepper2((pin1, pin2)) => {
-                pin1.enable();
-                pin2.enable();
-            }
-        }
-    }
+// What the coils do while the stepper stands still.  Handled by
+// `Stepper::poll`, which has to keep being called between moves; only an
+// immediate release also applies without it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct StepperHold {
+    // Coil duty, scaled from full current, once still for `settle_ms`.
+    pub duty: u8,
+    pub settle_ms: u16,
+    // Releases the coils once still this long, leaving the shaft free.
+    pub release_ms: Option<u16>,
+}
 
-    pub fn disable(&mut self) {
-        match self {
-            Self::Stepper1((pin1, pin2)) => {
-                pin1.disable();
-                pin2.disable();
-            }
-            Self::Stepper2((pin1, pin2)) => {
-                pin1.disable();
-                pin2.disable();
-            }
+impl StepperHold {
+    // Keep the coils at full current so the shaft holds its position.
+    pub const ENERGIZED: Self = Self { duty: u8::MAX, settle_ms: 0, release_ms: None };
+    // Release the coils as soon as a move ends.
+    pub const RELEASE: Self = Self { duty: u8::MAX, settle_ms: 0, release_ms: Some(0) };
+}
+
+// Stepper parameters carried by `MotorPort::SingleStepper`.
+#[derive(Clone, Copy)]
+pub struct StepperConfig {
+    pub steps_per_rev: u16,
+    // Style of `Stepper::move_to`.
+    pub style: StepperStyle,
+    pub hold: StepperHold,
+    pub microsteps: MicrostepResolution,
+    pub curve: MicrostepCurve,
+}
+
+impl StepperConfig {
+    // A motor with `steps_per_rev` full steps per revolution, stepping
+    // SINGLE, holding its position between moves and microstepping 16 times
+    // along a sine.
+    pub const fn new(steps_per_rev: u16) -> Self {
+        Self {
+            steps_per_rev,
+            style: StepperStyle::SINGLE,
+            hold: StepperHold::ENERGIZED,
+            microsteps: MicrostepResolution::X16,
+            curve: MicrostepCurve::Sine,
         }
     }
 }
 
-pub struct Stepper {
-    pin: StepperPin,
-    output: *mut DigitalOutput,
+// How `Stepper::home` looks for its endstop.  Speeds are in full steps per
+// second, distances in full steps.
+#[derive(Clone, Copy)]
+pub struct HomingConfig {
+    // Towards the endstop.
+    pub dir: StepperDirection,
+    pub style: StepperStyle,
+    // Level the endstop pin reads while pressed.
+    pub triggered: PinState,
+    // First approach, and the slower second one that sets the zero.
+    pub speed: u16,
+    pub slow_speed: u16,
+    // Distance backed off between the two approaches.
+    pub backoff: u32,
+    // Furthest the first approach may travel without a hit.
+    pub max_travel: u32,
+}
+
+// A move started by `Stepper::move_to`, advanced by `Stepper::poll`.
+#[derive(Clone, Copy)]
+struct Motion {
+    // Absolute position to stop at, and the direction currently travelled.
+    target: i32,
+    dir: StepperDirection,
+    style: StepperStyle,
+    // Time between `onestep` calls, and when the last one was issued.
+    interval_us: u32,
+    last_us: Option<u32>,
+    // Set when the move speeds up and slows down under `set_acceleration`.
+    ramp: Option<Ramp>,
+}
+
+// Per-stepper state kept by the shield between calls.
+pub struct StepperChannel<P> {
+    pins: (P, P),
+    id: StepperId,
     // # steps per revolution
     revsteps: u16,
+    style: StepperStyle,
+    hold: StepperHold,
+    microsteps: MicrostepResolution,
+    curve: MicrostepCurve,
     usperstep: u32,
+    // In full steps/s^2; zero runs every move at the `set_speed` rate.
+    acceleration: u16,
     steppingcounter: u32,
-    currentstep: u8
+    currentstep: u16,
+    // Absolute shaft position in microsteps of the configured resolution.
+    position: i32,
+    motion: Option<Motion>,
+    // Duties the last step set, whether its coils are still latched, since
+    // when they have stood still, and whether the hold duty is applied.
+    duties: (u8, u8),
+    energized: bool,
+    idle_since: Option<u32>,
+    holding: bool,
 }
 
-impl Stepper {
-    pub fn new(pin: StepperPin, steps: u16, output: *mut DigitalOutput) -> Self {
-        let mut me = Self {
-            pin,
-            output,
-            revsteps: steps,
+impl<P: PwmChannel> StepperChannel<P> {
+    pub(crate) fn new(pins: (P, P), id: StepperId, config: StepperConfig) -> Self {
+        Self {
+            pins,
+            id,
+            revsteps: config.steps_per_rev,
+            style: config.style,
+            hold: config.hold,
+            microsteps: config.microsteps,
+            curve: config.curve,
             usperstep: 0,
+            acceleration: 0,
             steppingcounter: 0,
-            currentstep: 0
-        };
+            currentstep: 0,
+            position: 0,
+            motion: None,
+            duties: (u8::MAX, u8::MAX),
+            energized: false,
+            idle_since: None,
+            holding: false,
+        }
+    }
+
+    pub(crate) fn into_pins(self) -> (P, P) {
+        self.pins
+    }
+
+    fn set_dutys(&mut self, duty1: u8, duty2: u8) {
+        self.duties = (duty1, duty2);
+        self.write_dutys(duty1, duty2);
+    }
+
+    fn write_dutys(&mut self, duty1: u8, duty2: u8) {
+        let _ = self.pins.0.set_duty_cycle_fraction(duty1 as u16, u8::MAX as u16);
+        let _ = self.pins.1.set_duty_cycle_fraction(duty2 as u16, u8::MAX as u16);
+    }
+}
+
+// A stepper borrowed from the shield together with the shared latch.
+pub struct Stepper<'a, P, T> {
+    channel: &'a mut StepperChannel<P>,
+    output: &'a mut DigitalOutput<T>,
+}
 
-        me.release();
-        me.pin.set_dutys(255, 255);
+impl<'a, P: PwmChannel, T: ShiftOut> Stepper<'a, P, T> {
+    pub(crate) fn new(channel: &'a mut StepperChannel<P>, output: &'a mut DigitalOutput<T>) -> Self {
+        Self {
+            channel,
+            output,
+        }
+    }
 
-        me
+    // Puts freshly built coils into a known state: released, at full duty.
+    pub(crate) fn init(&mut self) {
+        self.release();
+        self.channel.set_dutys(255, 255);
     }
 
     pub fn set_speed(&mut self, rpm: u16) {
-        self.usperstep = (60_000_000_u32 / (self.revsteps * rpm) as u32).into();
-        self.steppingcounter = 0;
+        self.channel.usperstep = 60_000_000_u32 / (self.channel.revsteps as u32 * rpm.max(1) as u32).max(1);
+        self.channel.steppingcounter = 0;
+    }
+
+    // Same as `set_speed`, in full steps per second.  With an acceleration
+    // set this is the cruising speed.
+    pub fn set_max_speed(&mut self, steps_per_second: u16) {
+        self.channel.usperstep = 1_000_000 / steps_per_second.max(1) as u32;
+        self.channel.steppingcounter = 0;
+    }
+
+    // Makes moves ramp up to the set speed and back down at
+    // `steps_per_second2` full steps/s^2; zero moves at constant speed.
+    pub fn set_acceleration(&mut self, steps_per_second2: u16) {
+        self.channel.acceleration = steps_per_second2;
+    }
+
+    pub fn style(&self) -> StepperStyle {
+        self.channel.style
+    }
+
+    // Changes the style of later `move_to` calls.
+    pub fn set_style(&mut self, style: StepperStyle) {
+        self.channel.style = style;
     }
 
-    pub fn release(&self) {
-        let (a, b, c, d)= self.pin.get_abcd();
-        let output = unsafe { self.output.as_mut().unwrap()};
+    pub fn release(&mut self) {
+        let (a, b, c, d)= self.channel.id.get_abcd();
 
         // all motor pins to 0
-        output.and(!a & !b & !c & !d);
-        output.transmit();
+        self.output.and(!a & !b & !c & !d);
+        self.output.transmit();
+
+        self.channel.energized = false;
+        self.channel.idle_since = None;
+        self.channel.holding = false;
+    }
+
+    // `onestep` calls and time between them for `steps` full steps in `style`.
+    fn onesteps(&self, steps: u32, style: StepperStyle) -> (u32, u32) {
+        let microsteps = self.microsteps_per_step();
+
+        (style.onesteps(steps, microsteps), self.channel.usperstep / style.rate(microsteps))
     }
 
-    pub fn step(&mut self, mut steps: u32, dir: StepperDirection, style: StepperStyle) {
-        let mut uspers: u32 = self.usperstep;
+    // Microsteps the next `onestep` in `style` moves the shaft: SINGLE and
+    // DOUBLE take a half step first when between their usual phases.
+    fn step_size(&self, style: StepperStyle) -> i32 {
+        let microsteps = self.microsteps_per_step();
+        let odd = (self.channel.currentstep / (microsteps / 2)) % 2 == 1;
 
         match style {
-            StepperStyle::INTERLEAVE => {
-                uspers /= 2;
-            }
-            StepperStyle::MICROSTEP => {
-                uspers /= MICROSTEPS as u32;
-                steps *= MICROSTEPS as u32;
+            StepperStyle::SINGLE if odd => microsteps as i32 / 2,
+            StepperStyle::DOUBLE if !odd => microsteps as i32 / 2,
+            _ => style.granularity(microsteps),
+        }
+    }
+
+    pub fn step(&mut self, steps: u32, dir: StepperDirection, style: StepperStyle, delay: &mut impl DelayNs) {
+        if self.channel.acceleration != 0 {
+            // Ramped moves share the polled engine, with the waits spent here.
+            self.start_move(self.relative_target(steps, dir, style), style);
+
+            while self.advance() {
+                delay.delay_us(self.channel.motion.map_or(0, |motion| motion.interval_us));
             }
-            _ => { }
+
+            return;
         }
 
+        let (mut steps, uspers) = self.onesteps(steps, style);
+
         while steps > 0 {
             _ = self.onestep(dir, style);
-            delay_ms((uspers / 1000) as u16); // in ms
-            self.steppingcounter += uspers % 1000;
-            if self.steppingcounter >= 1000 {
-                delay_ms(1);
-                self.steppingcounter -= 1000;
+            delay.delay_ms(uspers / 1000); // in ms
+            self.channel.steppingcounter += uspers % 1000;
+            if self.channel.steppingcounter >= 1000 {
+                delay.delay_ms(1);
+                self.channel.steppingcounter -= 1000;
             }
             steps -= 1;
         }
 
+        // Runs on to the next full step, as a ramped or polled move does.
         if let StepperStyle::MICROSTEP = style {
-            let mut ret = self.onestep(dir, style);
-            while ret != 0 && ret != MICROSTEPS {
-                ret = self.onestep(dir, style);
-                delay_ms((uspers / 1000) as u16); // in ms
-                self.steppingcounter += uspers % 1000;
-                if self.steppingcounter >= 1000 {
-                    delay_ms(1);
-                    self.steppingcounter -= 1000;
+            while self.channel.currentstep % self.microsteps_per_step() != 0 {
+                self.onestep(dir, style);
+                delay.delay_ms(uspers / 1000); // in ms
+                self.channel.steppingcounter += uspers % 1000;
+                if self.channel.steppingcounter >= 1000 {
+                    delay.delay_ms(1);
+                    self.channel.steppingcounter -= 1000;
                 }
             }
         }
 
+        if self.channel.hold.release_ms == Some(0) {
+            self.release();
+        }
+    }
+
+    // Starts moving `steps` full steps in `style`, like `step` but without
+    // blocking: `poll` issues each step when it is due.  Microstepped moves
+    // run on to the next full step.  Otherwise behaves as `move_to`.
+    pub fn move_relative(&mut self, steps: u32, dir: StepperDirection, style: StepperStyle) {
+        self.start_move(self.relative_target(steps, dir, style), style);
     }
 
-    pub fn onestep(&mut self, dir: StepperDirection, style: StepperStyle) -> u8 {
-        let (a, b, c, d)= self.pin.get_abcd();
-        let mut ocra: u8 = u8::max_value();
-        let mut ocrb: u8 = u8::max_value();
+    // Position `steps` steps of `style` away in `dir`, clamped to the range
+    // of positions.
+    fn relative_target(&self, steps: u32, dir: StepperDirection, style: StepperStyle) -> i32 {
+        let microsteps = self.microsteps_per_step() as i32;
+        let steps = i32::try_from(steps).unwrap_or(i32::MAX);
+        let span = match style {
+            StepperStyle::MICROSTEP => {
+                let phase = self.channel.currentstep as i32 % microsteps;
+                let run_on = match dir {
+                    StepperDirection::FORWARD => (microsteps - phase) % microsteps,
+                    StepperDirection::BACKWARD => phase,
+                };
+
+                steps.saturating_mul(microsteps).saturating_add(run_on)
+            }
+            _ if steps == 0 => 0,
+            _ => (steps - 1).saturating_mul(style.granularity(microsteps as u16)).saturating_add(self.step_size(style)),
+        };
+
+        self.channel.position.saturating_add(dir.sign() * span)
+    }
+
+    // Starts moving to the absolute `position`, in microsteps, in the
+    // stepper's style at the `set_speed` rate without blocking; `poll`
+    // issues each step when it is due.  Stops short of a position between
+    // two steps of the style.  A ramped move already under way in the same
+    // style carries on from its current speed, slowing down and turning
+    // round first if `position` is behind it; any other move in progress is
+    // replaced.
+    pub fn move_to(&mut self, position: i32) {
+        self.start_move(position, self.channel.style);
+    }
+
+    fn start_move(&mut self, position: i32, style: StepperStyle) {
+        if let Some(motion) = self.channel.motion.as_mut() {
+            if motion.ramp.is_some() && motion.style == style {
+                motion.target = position;
+                return;
+            }
+        }
+
+        let distance = position.saturating_sub(self.channel.position);
+        let (_, interval_us) = self.onesteps(0, style);
+        let acceleration = self.channel.acceleration as u32 * style.rate(self.microsteps_per_step());
+
+        self.channel.motion = (distance.unsigned_abs() >= self.step_size(style) as u32).then_some(Motion {
+            target: position,
+            dir: if distance < 0 { StepperDirection::BACKWARD } else { StepperDirection::FORWARD },
+            style,
+            interval_us,
+            last_us: None,
+            ramp: (acceleration != 0).then(|| Ramp::new(acceleration, interval_us)),
+        });
+    }
+
+    // Brings a ramped move to a standstill as quickly as the acceleration
+    // allows; any other move stops at once.
+    pub fn stop(&mut self) {
+        let position = self.channel.position;
+        let microsteps = self.microsteps_per_step();
+
+        if let Some(motion) = self.channel.motion.as_mut() {
+            match motion.ramp {
+                Some(ramp) => {
+                    let span = (ramp.steps_to_stop() as i32).saturating_mul(motion.style.granularity(microsteps));
+
+                    motion.target = position.saturating_add(motion.dir.sign() * span);
+                }
+                None => self.channel.motion = None,
+            }
+        }
+    }
+
+    // Drives towards `endstop` at `config.speed` until it triggers, backs off
+    // `config.backoff` steps, approaches again at `config.slow_speed` and
+    // makes the trigger point position zero.  Blocks until done; cancels any
+    // move in progress.
+    pub fn home(
+        &mut self,
+        endstop: &mut impl InputPin,
+        config: &HomingConfig,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), HomingError> {
+        self.channel.motion = None;
+
+        if endstop_triggered(endstop, config)? {
+            return Err(HomingError::AlreadyTriggered);
+        }
+
+        if !self.seek(endstop, config, config.dir, config.speed, config.max_travel, Some(true), delay)? {
+            return Err(HomingError::TravelExceeded);
+        }
+
+        self.seek(endstop, config, config.dir.reversed(), config.speed, config.backoff, None, delay)?;
+
+        if endstop_triggered(endstop, config)? {
+            return Err(HomingError::NotReleased);
+        }
+
+        // The endstop is at most `backoff` steps away, give or take a step.
+        if !self.seek(endstop, config, config.dir, config.slow_speed, config.backoff.saturating_add(1), Some(true), delay)? {
+            return Err(HomingError::TravelExceeded);
+        }
+
+        self.set_current_position(0);
+
+        Ok(())
+    }
+
+    // Steps up to `steps` full steps at `speed`, stopping early once the
+    // endstop reads `until`.  Returns whether it did.
+    #[allow(clippy::too_many_arguments)]
+    fn seek(
+        &mut self,
+        endstop: &mut impl InputPin,
+        config: &HomingConfig,
+        dir: StepperDirection,
+        speed: u16,
+        steps: u32,
+        until: Option<bool>,
+        delay: &mut impl DelayNs,
+    ) -> Result<bool, HomingError> {
+        let start = self.channel.position;
+        let microsteps = self.microsteps_per_step();
+        let travel = steps.saturating_mul(microsteps as u32);
+        let wait_us = 1_000_000 / (speed.max(1) as u32 * config.style.rate(microsteps));
+
+        loop {
+            let triggered = endstop_triggered(endstop, config)?;
+
+            if until == Some(triggered) {
+                return Ok(true);
+            }
+
+            if self.channel.position.abs_diff(start) + self.step_size(config.style) as u32 > travel {
+                return Ok(false);
+            }
+
+            self.onestep(dir, config.style);
+            delay.delay_us(wait_us);
+        }
+    }
+
+    // Absolute position in microsteps, `microsteps_per_step` to a full step.
+    pub fn current_position(&self) -> i32 {
+        self.channel.position
+    }
+
+    // Redefines the current shaft position, cancelling any move in progress.
+    pub fn set_current_position(&mut self, position: i32) {
+        self.channel.position = position;
+        self.channel.motion = None;
+    }
+
+    // Where the move in progress ends, or the current position when idle.
+    pub fn target_position(&self) -> i32 {
+        self.channel.motion.map_or(self.channel.position, |motion| motion.target)
+    }
+
+    pub fn distance_to_go(&self) -> i32 {
+        self.target_position().saturating_sub(self.channel.position)
+    }
+
+    pub fn microsteps_per_step(&self) -> u16 {
+        self.channel.microsteps.microsteps()
+    }
+
+    // Issues the next step of the current move if it is due at `now_us`, a
+    // free-running microsecond clock that may wrap.  The first step goes out
+    // on the first poll.  Returns whether the move is still in progress.
+    pub fn poll(&mut self, now_us: u32) -> bool {
+        let Some(motion) = self.channel.motion.as_mut() else {
+            self.idle(now_us);
+            return false;
+        };
+
+        if let Some(last_us) = motion.last_us {
+            if now_us.wrapping_sub(last_us) < motion.interval_us {
+                return true;
+            }
+        }
+
+        motion.last_us = Some(now_us);
+        self.advance()
+    }
+
+    // Takes the next step of the current move and works out the wait before
+    // the one after it.  Returns whether the move goes on.
+    fn advance(&mut self) -> bool {
+        let Some(mut motion) = self.channel.motion else {
+            return false;
+        };
+
+        // The target may have moved since the last step.
+        if self.arrived(&motion) {
+            return self.finish();
+        }
+
+        if self.ahead(&motion) < 0 && self.settled(&motion) {
+            motion.dir = motion.dir.reversed();
+
+            if let Some(ramp) = motion.ramp.as_mut() {
+                ramp.restart();
+            }
+        }
+
+        self.onestep(motion.dir, motion.style);
+
+        if self.arrived(&motion) {
+            return self.finish();
+        }
+
+        let remaining = self.ahead(&motion).max(0) / motion.style.granularity(self.microsteps_per_step());
+
+        if let Some(ramp) = motion.ramp.as_mut() {
+            motion.interval_us = ramp.next_interval(remaining as u32);
+        }
+
+        self.channel.motion = Some(motion);
+
+        true
+    }
+
+    // Ends the current move, leaving the coils as configured.
+    fn finish(&mut self) -> bool {
+        if let Some(motion) = self.channel.motion.take() {
+            self.channel.idle_since = motion.last_us;
+        }
+
+        if self.channel.hold.release_ms == Some(0) {
+            self.release();
+        }
+
+        false
+    }
+
+    // Applies the hold duty and then releases the coils of a stepper left
+    // standing, as set by its `StepperHold`.  Still time is counted from the
+    // last polled step, or from this poll after a blocking move.
+    fn idle(&mut self, now_us: u32) {
+        if !self.channel.energized {
+            return;
+        }
+
+        let hold = self.channel.hold;
+        let since = *self.channel.idle_since.get_or_insert(now_us);
+        let idle_ms = now_us.wrapping_sub(since) / 1000;
+
+        if hold.release_ms.is_some_and(|ms| idle_ms >= ms as u32) {
+            self.release();
+        } else if !self.channel.holding && hold.duty != u8::MAX && idle_ms >= hold.settle_ms as u32 {
+            let (duty1, duty2) = self.channel.duties;
+            let scale = |duty: u8| (duty as u16 * hold.duty as u16 / u8::MAX as u16) as u8;
+
+            self.channel.write_dutys(scale(duty1), scale(duty2));
+            self.channel.holding = true;
+        }
+    }
+
+    // Microsteps to the target in the direction of travel.
+    fn ahead(&self, motion: &Motion) -> i32 {
+        motion.target.saturating_sub(self.channel.position).saturating_mul(motion.dir.sign())
+    }
+
+    // Slow enough to stop or turn round on the spot.
+    fn settled(&self, motion: &Motion) -> bool {
+        !matches!(motion.ramp, Some(ramp) if ramp.steps_to_stop() > 1)
+    }
+
+    fn arrived(&self, motion: &Motion) -> bool {
+        self.ahead(motion).unsigned_abs() < self.step_size(motion.style) as u32 && self.settled(motion)
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.channel.motion.is_some()
+    }
+
+    pub fn onestep(&mut self, dir: StepperDirection, style: StepperStyle) -> u16 {
+        let (a, b, c, d)= self.channel.id.get_abcd();
+        let previous = self.channel.currentstep;
+        let microsteps = self.microsteps_per_step();
+        let curve = self.channel.curve;
+        let mut ocra: u8 = u8::MAX;
+        let mut ocrb: u8 = u8::MAX;
 
         match style {
             StepperStyle::SINGLE => {
-                if (self.currentstep / (MICROSTEPS / 2)) % 2 == 0 {
+                if (self.channel.currentstep / (microsteps / 2)) % 2 == 0 {
                     // Go to the next even step
-                    self.currentstep = match dir {
-                        StepperDirection::FORWARD => self.currentstep.wrapping_add(MICROSTEPS),
-                        StepperDirection::BACKWARD => self.currentstep.wrapping_sub(MICROSTEPS),
+                    self.channel.currentstep = match dir {
+                        StepperDirection::FORWARD => self.channel.currentstep.wrapping_add(microsteps),
+                        StepperDirection::BACKWARD => self.channel.currentstep.wrapping_sub(microsteps),
                     };
                 } else {
                     // We're at an odd step, weird
-                    self.currentstep = match dir {
-                        StepperDirection::FORWARD => self.currentstep.wrapping_add(MICROSTEPS / 2),
-                        StepperDirection::BACKWARD => self.currentstep.wrapping_sub(MICROSTEPS / 2),
+                    self.channel.currentstep = match dir {
+                        StepperDirection::FORWARD => self.channel.currentstep.wrapping_add(microsteps / 2),
+                        StepperDirection::BACKWARD => self.channel.currentstep.wrapping_sub(microsteps / 2),
                     };
                 }
             },
             StepperStyle::DOUBLE => {
-                if ((self.currentstep / (MICROSTEPS / 2)) % 2) == 0 {
+                if ((self.channel.currentstep / (microsteps / 2)) % 2) == 0 {
                     // We're at an odd step, weird
-                    self.currentstep = match dir {
-                        StepperDirection::FORWARD => self.currentstep.wrapping_add(MICROSTEPS / 2),
-                        StepperDirection::BACKWARD => self.currentstep.wrapping_sub(MICROSTEPS / 2),
+                    self.channel.currentstep = match dir {
+                        StepperDirection::FORWARD => self.channel.currentstep.wrapping_add(microsteps / 2),
+                        StepperDirection::BACKWARD => self.channel.currentstep.wrapping_sub(microsteps / 2),
                     };
                 } else {
                     // Go to the next even step
-                    self.currentstep = match dir {
-                        StepperDirection::FORWARD => self.currentstep.wrapping_add(MICROSTEPS),
-                        StepperDirection::BACKWARD => self.currentstep.wrapping_sub(MICROSTEPS),
+                    self.channel.currentstep = match dir {
+                        StepperDirection::FORWARD => self.channel.currentstep.wrapping_add(microsteps),
+                        StepperDirection::BACKWARD => self.channel.currentstep.wrapping_sub(microsteps),
                     };
                 }
             },
             StepperStyle::INTERLEAVE => {
-                self.currentstep = match dir {
-                    StepperDirection::FORWARD => self.currentstep.wrapping_add(MICROSTEPS / 2),
-                    StepperDirection::BACKWARD => self.currentstep.wrapping_sub(MICROSTEPS / 2),
+                self.channel.currentstep = match dir {
+                    StepperDirection::FORWARD => self.channel.currentstep.wrapping_add(microsteps / 2),
+                    StepperDirection::BACKWARD => self.channel.currentstep.wrapping_sub(microsteps / 2),
                 }
             },
             StepperStyle::MICROSTEP => {
-                self.currentstep = match dir {
-                    StepperDirection::FORWARD => self.currentstep.wrapping_add(1),
-                    StepperDirection::BACKWARD => self.currentstep.wrapping_sub(1),
+                self.channel.currentstep = match dir {
+                    StepperDirection::FORWARD => self.channel.currentstep.wrapping_add(1),
+                    StepperDirection::BACKWARD => self.channel.currentstep.wrapping_sub(1),
                 };
 
-                self.currentstep = self.currentstep.wrapping_add(MICROSTEPS * 4) % (MICROSTEPS * 4);
+                self.channel.currentstep = self.channel.currentstep.wrapping_add(microsteps * 4) % (microsteps * 4);
 
                 ocra = 0;
                 ocrb = 0;
-                if self.currentstep < MICROSTEPS {
-                    ocra = MICROSTEP_CURVE[(MICROSTEPS - self.currentstep) as usize];
-                    ocrb = MICROSTEP_CURVE[self.currentstep as usize];
-                } else if self.currentstep < MICROSTEPS * 2 {
-                    ocra = MICROSTEP_CURVE[(self.currentstep - MICROSTEPS) as usize];
-                    ocrb = MICROSTEP_CURVE[(MICROSTEPS * 2 - self.currentstep) as usize];
-                } else if self.currentstep < MICROSTEPS * 3 {
-                    ocra = MICROSTEP_CURVE[(MICROSTEPS * 3 - self.currentstep) as usize];
-                    ocrb = MICROSTEP_CURVE[(self.currentstep - MICROSTEPS * 2) as usize];
-                } else if self.currentstep < MICROSTEPS * 4 {
-                    ocra = MICROSTEP_CURVE[(self.currentstep - MICROSTEPS * 3) as usize];
-                    ocrb = MICROSTEP_CURVE[(MICROSTEPS * 4 - self.currentstep) as usize];
+                if self.channel.currentstep < microsteps {
+                    ocra = curve.duty(microsteps - self.channel.currentstep, microsteps);
+                    ocrb = curve.duty(self.channel.currentstep, microsteps);
+                } else if self.channel.currentstep < microsteps * 2 {
+                    ocra = curve.duty(self.channel.currentstep - microsteps, microsteps);
+                    ocrb = curve.duty(microsteps * 2 - self.channel.currentstep, microsteps);
+                } else if self.channel.currentstep < microsteps * 3 {
+                    ocra = curve.duty(microsteps * 3 - self.channel.currentstep, microsteps);
+                    ocrb = curve.duty(self.channel.currentstep - microsteps * 2, microsteps);
+                } else if self.channel.currentstep < microsteps * 4 {
+                    ocra = curve.duty(self.channel.currentstep - microsteps * 3, microsteps);
+                    ocrb = curve.duty(microsteps * 4 - self.channel.currentstep, microsteps);
                 }
             }
         }
 
-        self.currentstep = self.currentstep % (MICROSTEPS * 4);
+        self.channel.currentstep %= microsteps * 4;
+
+        // No style moves more than a full step, so the phase change gives the
+        // distance travelled.
+        let moved = self.channel.currentstep.wrapping_sub(previous) % (microsteps * 4);
+        self.channel.position = self.channel.position.saturating_add(if moved <= microsteps {
+            moved as i32
+        } else {
+            moved as i32 - (microsteps * 4) as i32
+        });
 
-        self.pin.set_dutys(ocra, ocrb);
+        self.channel.set_dutys(ocra, ocrb);
 
-        let output = unsafe { self.output.as_mut().unwrap()};
         // release all
-        output.and(!a & !b & !c & !d); // all motor pins to 0
+        self.output.and(!a & !b & !c & !d); // all motor pins to 0
 
         if style == StepperStyle::MICROSTEP {
-            match (self.currentstep / MICROSTEPS) % 4 {
-                0 => output.or(a | b),
-                1 => output.or(b | c),
-                2 => output.or(c | d),
-                3 => output.or(d | a),
+            match (self.channel.currentstep / microsteps) % 4 {
+                0 => self.output.or(a | b),
+                1 => self.output.or(b | c),
+                2 => self.output.or(c | d),
+                3 => self.output.or(d | a),
                 _ => { }
             }
         } else {
-            match self.currentstep / (MICROSTEPS / 2) {
-                0 => output.or(a),     // energize coil 1 only
-                1 => output.or(a | b), // energize coil 1 + 2
-                2 => output.or(b),     // energize coil 2 only
-                3 => output.or(b | c), // energize coil 2 + 3
-                4 => output.or(c),     // energize coil 3 only
-                5 => output.or(c | d), // energize coil 3 + 4
-                6 => output.or(d),     // energize coil 4 only
-                7 => output.or(d | a), // energize coil 1 + 4
-                _ => output.and(!a & !b & !c & !d) // all motor pins to 0
+            match self.channel.currentstep / (microsteps / 2) {
+                0 => self.output.or(a),     // energize coil 1 only
+                1 => self.output.or(a | b), // energize coil 1 + 2
+                2 => self.output.or(b),     // energize coil 2 only
+                3 => self.output.or(b | c), // energize coil 2 + 3
+                4 => self.output.or(c),     // energize coil 3 only
+                5 => self.output.or(c | d), // energize coil 3 + 4
+                6 => self.output.or(d),     // energize coil 4 only
+                7 => self.output.or(d | a), // energize coil 1 + 4
+                _ => self.output.and(!a & !b & !c & !d) // all motor pins to 0
             }
         }
 
-        output.transmit();
+        self.output.transmit();
 
-        self.currentstep
+        self.channel.energized = true;
+        self.channel.idle_since = None;
+        self.channel.holding = false;
+
+        self.channel.currentstep
     }
 
     pub fn enable(&mut self) {
-        self.pin.enable();
+        self.channel.pins.0.enable();
+        self.channel.pins.1.enable();
     }
 
     pub fn disable(&mut self) {
-        self.pin.disable();
+        self.channel.pins.0.disable();
+        self.channel.pins.1.disable();
     }
-}
\ No newline at end of file
+}
+
+fn endstop_triggered(endstop: &mut impl InputPin, config: &HomingConfig) -> Result<bool, HomingError> {
+    let high = endstop.is_high().map_err(|_| HomingError::Endstop)?;
+
+    Ok(PinState::from(high) == config.triggered)
+}
@@ -0,0 +1,497 @@
+use core::{cell::Cell, convert::Infallible};
+
+use arduino_hal::{
+    hal::port,
+    pac::{tc0, tc1, tc2, PORTB, PORTD, TC0, TC1, TC2},
+    port::{mode, Pin},
+};
+use avr_device::interrupt::{self, Mutex};
+use embedded_hal::pwm::{ErrorType, SetDutyCycle};
+
+#[cfg(not(feature = "fast-shift"))]
+use super::digital_output::BitBang;
+#[cfg(feature = "fast-shift")]
+use super::port_shift::PortShift;
+#[cfg(feature = "clock")]
+use super::clock::Clock;
+use super::{board::Board, layout::{Port1Frequency, Port2Frequency, PortLayout, ShieldLayout}, pwm::PwmChannel, MotorShield};
+
+// The Adafruit Motor Shield v1 on an Arduino Uno.
+pub struct ArduinoUno;
+
+impl Board for ArduinoUno {
+    type MotorPwm = UnoMotorPwm;
+    type ServoPwm = UnoServoPwm;
+    type Shift = UnoShiftOut;
+    type Timers = UnoTimers;
+}
+
+#[cfg(not(feature = "fast-shift"))]
+pub type UnoShiftOut = BitBang<
+    Pin<mode::Output, port::PD4>,
+    Pin<mode::Output, port::PB4>,
+    Pin<mode::Output, port::PB0>,
+    Pin<mode::Output, port::PD7>,
+>;
+
+#[cfg(feature = "fast-shift")]
+pub type UnoShiftOut = PortShift;
+
+type Floating<P> = Pin<mode::Input<mode::Floating>, P>;
+
+// The timers a shield runs its PWM on: Timer2 for port 1, Timer0 for port 2
+// and Timer1 for the servos.  `None` for those its layout leaves unused.
+pub struct UnoTimers {
+    tc0: Option<TC0>,
+    tc1: Option<TC1>,
+    tc2: Option<TC2>,
+    // Prescaler of the timer `Clock` runs on, `None` when it is not running.
+    #[cfg(feature = "clock")]
+    clock_prescaler: Option<u16>,
+}
+
+// Timers and shield pins a `MotorShield<ArduinoUno>` does not hold: from
+// `new` those its layout leaves unused, from `free` everything it held.
+// Pins come back as floating inputs, ready to be handed to `new` again.
+#[derive(Default)]
+pub struct UnoLeftovers {
+    pub tc0: Option<TC0>,
+    pub tc1: Option<TC1>,
+    pub tc2: Option<TC2>,
+    pub d3: Option<Floating<port::PD3>>,
+    pub d4: Option<Floating<port::PD4>>,
+    pub d5: Option<Floating<port::PD5>>,
+    pub d6: Option<Floating<port::PD6>>,
+    pub d7: Option<Floating<port::PD7>>,
+    pub d8: Option<Floating<port::PB0>>,
+    pub d9: Option<Floating<port::PB1>>,
+    pub d10: Option<Floating<port::PB2>>,
+    pub d11: Option<Floating<port::PB3>>,
+    pub d12: Option<Floating<port::PB4>>,
+}
+
+// Shield pins by their bit in PORTB (D8 to D12) and PORTD (D3 to D7).
+const D3: u8 = 1 << 3;
+const D4: u8 = 1 << 4;
+const D5: u8 = 1 << 5;
+const D6: u8 = 1 << 6;
+const D7: u8 = 1 << 7;
+const D8: u8 = 1 << 0;
+const D9: u8 = 1 << 1;
+const D10: u8 = 1 << 2;
+const D11: u8 = 1 << 3;
+const D12: u8 = 1 << 4;
+
+// Pins held by the shield `new` built, until `free`, for `emergency_stop`.
+#[derive(Clone, Copy)]
+struct Claimed {
+    portb: u8,
+    portd: u8,
+}
+
+static CLAIMED: Mutex<Cell<Claimed>> = Mutex::new(Cell::new(Claimed { portb: 0, portd: 0 }));
+
+// Timer0 and Timer2 in 8-bit fast PWM, 16 MHz / 256 divided by the
+// prescaler: 62.5 kHz unscaled, 7.8 kHz at /8, 1.95 kHz at /32 and 977 Hz at
+// /64.  Both outputs start disconnected.
+fn init_timer0(tc0: &TC0, frequency: Port2Frequency) {
+    tc0.tccr0a.write(|w| w.wgm0().pwm_fast().com0a().disconnected().com0b().disconnected());
+    tc0.tccr0b.write(|w| match frequency {
+        Port2Frequency::Khz64 => w.cs0().direct(),
+        Port2Frequency::Khz8 => w.cs0().prescale_8(),
+        Port2Frequency::Khz1 => w.cs0().prescale_64(),
+    });
+}
+
+fn init_timer2(tc2: &TC2, frequency: Port1Frequency) {
+    tc2.tccr2a.write(|w| w.wgm2().pwm_fast().com2a().disconnected().com2b().disconnected());
+    tc2.tccr2b.write(|w| match frequency {
+        Port1Frequency::Khz64 => w.cs2().direct(),
+        Port1Frequency::Khz8 => w.cs2().prescale_8(),
+        Port1Frequency::Khz2 => w.cs2().prescale_32(),
+        Port1Frequency::Khz1 => w.cs2().prescale_64(),
+    });
+}
+
+// A timer is configured once when the shield is built and from then on only
+// shared between its two channels, each of which touches nothing but its own
+// compare register and output mode bits.
+pub(crate) fn timer0() -> &'static tc0::RegisterBlock {
+    unsafe { &*TC0::ptr() }
+}
+
+pub(crate) fn timer2() -> &'static tc2::RegisterBlock {
+    unsafe { &*TC2::ptr() }
+}
+
+pub enum UnoMotorPwm {
+    D11(Pin<mode::Output, port::PB3>),
+    D3(Pin<mode::Output, port::PD3>),
+    D6(Pin<mode::Output, port::PD6>),
+    D5(Pin<mode::Output, port::PD5>),
+}
+
+impl ErrorType for UnoMotorPwm {
+    type Error = Infallible;
+}
+
+impl SetDutyCycle for UnoMotorPwm {
+    fn max_duty_cycle(&self) -> u16 {
+        u8::MAX as u16
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        let duty = duty.min(u8::MAX as u16) as u8;
+
+        match self {
+            Self::D11(_) => timer2().ocr2a.write(|w| w.bits(duty)),
+            Self::D3(_) => timer2().ocr2b.write(|w| w.bits(duty)),
+            Self::D6(_) => timer0().ocr0a.write(|w| w.bits(duty)),
+            Self::D5(_) => timer0().ocr0b.write(|w| w.bits(duty)),
+        }
+
+        Ok(())
+    }
+}
+
+// The output mode bits of both channels of a timer share one register, so
+// they are changed with interrupts off: an `emergency_stop` from an interrupt
+// must not be undone by a write of the register read before it.
+impl PwmChannel for UnoMotorPwm {
+    fn enable(&mut self) {
+        interrupt::free(|_| match self {
+            Self::D11(_) => timer2().tccr2a.modify(|_, w| w.com2a().match_clear()),
+            Self::D3(_) => timer2().tccr2a.modify(|_, w| w.com2b().match_clear()),
+            Self::D6(_) => timer0().tccr0a.modify(|_, w| w.com0a().match_clear()),
+            Self::D5(_) => timer0().tccr0a.modify(|_, w| w.com0b().match_clear()),
+        })
+    }
+
+    fn disable(&mut self) {
+        interrupt::free(|_| match self {
+            Self::D11(_) => timer2().tccr2a.modify(|_, w| w.com2a().disconnected()),
+            Self::D3(_) => timer2().tccr2a.modify(|_, w| w.com2b().disconnected()),
+            Self::D6(_) => timer0().tccr0a.modify(|_, w| w.com0a().disconnected()),
+            Self::D5(_) => timer0().tccr0a.modify(|_, w| w.com0b().disconnected()),
+        })
+    }
+}
+
+// Timer1 top: 16 MHz / 8 counts 0.5 us steps, so one 20 ms servo frame is
+// 40000 counts and an n us pulse a compare value of 2n.
+const SERVO_TOP: u16 = 39_999;
+
+// Puts Timer1 in 16-bit fast PWM with ICR1 as top (mode 14), prescaled by 8.
+// Both outputs start disconnected.
+fn init_servo_timer(tc1: &TC1) {
+    tc1.icr1.write(|w| w.bits(SERVO_TOP));
+    tc1.tccr1a.write(|w| w.wgm1().bits(0b10).com1a().disconnected().com1b().disconnected());
+    tc1.tccr1b.write(|w| w.wgm1().bits(0b11).cs1().prescale_8());
+}
+
+fn timer1() -> &'static tc1::RegisterBlock {
+    unsafe { &*TC1::ptr() }
+}
+
+// D10 and D9 on Timer1 (OC1B and OC1A).
+pub enum UnoServoPwm {
+    D10(Pin<mode::Output, port::PB2>),
+    D9(Pin<mode::Output, port::PB1>),
+}
+
+impl ErrorType for UnoServoPwm {
+    type Error = Infallible;
+}
+
+impl SetDutyCycle for UnoServoPwm {
+    fn max_duty_cycle(&self) -> u16 {
+        SERVO_TOP
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        let duty = duty.min(SERVO_TOP);
+
+        match self {
+            Self::D10(_) => timer1().ocr1b.write(|w| w.bits(duty)),
+            Self::D9(_) => timer1().ocr1a.write(|w| w.bits(duty)),
+        }
+
+        Ok(())
+    }
+}
+
+// With interrupts off, as for `UnoMotorPwm`.
+impl PwmChannel for UnoServoPwm {
+    fn enable(&mut self) {
+        interrupt::free(|_| match self {
+            Self::D10(_) => timer1().tccr1a.modify(|_, w| w.com1b().match_clear()),
+            Self::D9(_) => timer1().tccr1a.modify(|_, w| w.com1a().match_clear()),
+        })
+    }
+
+    fn disable(&mut self) {
+        interrupt::free(|_| match self {
+            Self::D10(_) => timer1().tccr1a.modify(|_, w| w.com1b().disconnected()),
+            Self::D9(_) => timer1().tccr1a.modify(|_, w| w.com1a().disconnected()),
+        })
+    }
+}
+
+impl<P1: PortLayout, P2: PortLayout> MotorShield<ArduinoUno, P1, P2> {
+    // Builds a shield on the timers and pins `layout` uses and hands back the
+    // rest untouched: Timer2 is only claimed for port 1, Timer0 for port 2
+    // and Timer1 for the servos.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        layout: ShieldLayout<P1, P2>,
+        tc0: TC0,
+        tc1: TC1,
+        tc2: TC2,
+        pin_d3: Floating<port::PD3>,
+        pin_d4: Floating<port::PD4>,
+        pin_d5: Floating<port::PD5>,
+        pin_d6: Floating<port::PD6>,
+        pin_d7: Floating<port::PD7>,
+        pin_d8: Floating<port::PB0>,
+        pin_d9: Floating<port::PB1>,
+        pin_d10: Floating<port::PB2>,
+        pin_d11: Floating<port::PB3>,
+        pin_d12: Floating<port::PB4>,
+    ) -> (Self, UnoLeftovers) {
+        let mut leftovers = UnoLeftovers::default();
+        let (port1, port2) = (layout.port1.port(), layout.port2.port());
+
+        let shift = UnoShiftOut::new(
+            pin_d4.into_output_high(),
+            pin_d12.into_output_high(),
+            pin_d8.into_output_high(),
+            pin_d7.into_output_high(),
+        );
+
+        let tc2 = if port1.uses_first() || port1.uses_second() {
+            init_timer2(&tc2, layout.frequency1);
+            Some(tc2)
+        } else {
+            leftovers.tc2 = Some(tc2);
+            None
+        };
+
+        let tc0 = if port2.uses_first() || port2.uses_second() {
+            init_timer0(&tc0, layout.frequency2);
+            Some(tc0)
+        } else {
+            leftovers.tc0 = Some(tc0);
+            None
+        };
+
+        let tc1 = if layout.servo1 || layout.servo2 {
+            init_servo_timer(&tc1);
+            Some(tc1)
+        } else {
+            leftovers.tc1 = Some(tc1);
+            None
+        };
+
+        let d11 = if port1.uses_first() {
+            Some(UnoMotorPwm::D11(pin_d11.into_output()))
+        } else {
+            leftovers.d11 = Some(pin_d11);
+            None
+        };
+
+        let d3 = if port1.uses_second() {
+            Some(UnoMotorPwm::D3(pin_d3.into_output()))
+        } else {
+            leftovers.d3 = Some(pin_d3);
+            None
+        };
+
+        let d6 = if port2.uses_first() {
+            Some(UnoMotorPwm::D6(pin_d6.into_output()))
+        } else {
+            leftovers.d6 = Some(pin_d6);
+            None
+        };
+
+        let d5 = if port2.uses_second() {
+            Some(UnoMotorPwm::D5(pin_d5.into_output()))
+        } else {
+            leftovers.d5 = Some(pin_d5);
+            None
+        };
+
+        let d10 = if layout.servo1 {
+            Some(UnoServoPwm::D10(pin_d10.into_output()))
+        } else {
+            leftovers.d10 = Some(pin_d10);
+            None
+        };
+
+        let d9 = if layout.servo2 {
+            Some(UnoServoPwm::D9(pin_d9.into_output()))
+        } else {
+            leftovers.d9 = Some(pin_d9);
+            None
+        };
+
+        let held = |pwm: bool, bit: u8| if pwm { bit } else { 0 };
+        let claimed = Claimed {
+            portb: D8 | D12 | held(d9.is_some(), D9) | held(d10.is_some(), D10) | held(d11.is_some(), D11),
+            portd: D4 | D7 | held(d3.is_some(), D3) | held(d5.is_some(), D5) | held(d6.is_some(), D6),
+        };
+
+        interrupt::free(|cs| CLAIMED.borrow(cs).set(claimed));
+
+        #[cfg(all(feature = "clock", not(feature = "clock-timer0")))]
+        let clock_prescaler = tc2.as_ref().map(|_| layout.frequency1.prescaler());
+        #[cfg(feature = "clock-timer0")]
+        let clock_prescaler = tc0.as_ref().map(|_| layout.frequency2.prescaler());
+
+        let timers = UnoTimers {
+            tc0,
+            tc1,
+            tc2,
+            #[cfg(feature = "clock")]
+            clock_prescaler,
+        };
+        let shield = Self::build(layout, shift, timers, [d11, d3, d6, d5], [d10, d9]);
+
+        (shield, leftovers)
+    }
+
+    // Releases every output as `into_parts` does, stops the timers and hands
+    // back everything the shield held, for the application to reconfigure.
+    pub fn free(self) -> UnoLeftovers {
+        let (shift, timers, [d11, d3, d6, d5], [d10, d9]) = self.into_parts();
+        let (clock, latch, data, enable) = shift.release();
+
+        interrupt::free(|cs| CLAIMED.borrow(cs).set(Claimed { portb: 0, portd: 0 }));
+
+        if let Some(tc0) = &timers.tc0 {
+            tc0.tccr0a.reset();
+            tc0.tccr0b.reset();
+            tc0.timsk0.reset();
+        }
+
+        if let Some(tc1) = &timers.tc1 {
+            tc1.tccr1a.reset();
+            tc1.tccr1b.reset();
+            tc1.icr1.reset();
+        }
+
+        if let Some(tc2) = &timers.tc2 {
+            tc2.tccr2a.reset();
+            tc2.tccr2b.reset();
+            tc2.timsk2.reset();
+        }
+
+        UnoLeftovers {
+            tc0: timers.tc0,
+            tc1: timers.tc1,
+            tc2: timers.tc2,
+            d3: d3.and_then(|pwm| match pwm {
+                UnoMotorPwm::D3(pin) => Some(pin.into_floating_input()),
+                _ => None,
+            }),
+            d4: Some(clock.into_floating_input()),
+            d5: d5.and_then(|pwm| match pwm {
+                UnoMotorPwm::D5(pin) => Some(pin.into_floating_input()),
+                _ => None,
+            }),
+            d6: d6.and_then(|pwm| match pwm {
+                UnoMotorPwm::D6(pin) => Some(pin.into_floating_input()),
+                _ => None,
+            }),
+            d7: Some(enable.into_floating_input()),
+            d8: Some(data.into_floating_input()),
+            d9: d9.and_then(|pwm| match pwm {
+                UnoServoPwm::D9(pin) => Some(pin.into_floating_input()),
+                _ => None,
+            }),
+            d10: d10.and_then(|pwm| match pwm {
+                UnoServoPwm::D10(pin) => Some(pin.into_floating_input()),
+                _ => None,
+            }),
+            d11: d11.and_then(|pwm| match pwm {
+                UnoMotorPwm::D11(pin) => Some(pin.into_floating_input()),
+                _ => None,
+            }),
+            d12: Some(latch.into_floating_input()),
+        }
+    }
+}
+
+impl MotorShield<ArduinoUno> {
+    // Forces every output of the shield off through the registers alone, so
+    // it can be called from any context: a panic handler, an interrupt, or
+    // while the shield is borrowed.  Every PWM pin the shield holds is
+    // disconnected from its timer and driven low, which takes the enable off
+    // every bridge and stops the servo pulses, then zero is latched onto the
+    // 74HC595.  Pins and timers the layout left to the application are not
+    // touched, and nothing is when no shield has been built.  A shield still
+    // in use does not know: it has to be built anew, or stopped and
+    // re-enabled, before it drives again.
+    pub fn emergency_stop() {
+        interrupt::free(|cs| {
+            let Claimed { portb, portd } = CLAIMED.borrow(cs).get();
+
+            if portb & D11 != 0 {
+                timer2().tccr2a.modify(|_, w| w.com2a().disconnected());
+            }
+            if portd & D3 != 0 {
+                timer2().tccr2a.modify(|_, w| w.com2b().disconnected());
+            }
+            if portd & D6 != 0 {
+                timer0().tccr0a.modify(|_, w| w.com0a().disconnected());
+            }
+            if portd & D5 != 0 {
+                timer0().tccr0a.modify(|_, w| w.com0b().disconnected());
+            }
+            if portb & D9 != 0 {
+                timer1().tccr1a.modify(|_, w| w.com1a().disconnected());
+            }
+            if portb & D10 != 0 {
+                timer1().tccr1a.modify(|_, w| w.com1b().disconnected());
+            }
+
+            if portd & D4 == 0 {
+                return;
+            }
+
+            // Every claimed pin low: D8 (data), D12 (latch), D4 (clock) and the
+            // PWM pins, and D7, whose low output enable lets the zero byte
+            // through.
+            unsafe {
+                let portb_regs = &*PORTB::ptr();
+                let portd_regs = &*PORTD::ptr();
+
+                portb_regs.portb.modify(|r, w| w.bits(r.bits() & !portb));
+                portb_regs.ddrb.modify(|r, w| w.bits(r.bits() | portb));
+                portd_regs.portd.modify(|r, w| w.bits(r.bits() & !portd));
+                portd_regs.ddrd.modify(|r, w| w.bits(r.bits() | portd));
+
+                // Eight zero bits, then the rising latch edge.
+                for _ in 0..8 {
+                    portd_regs.portd.modify(|r, w| w.bits(r.bits() & !D4));
+                    portd_regs.portd.modify(|r, w| w.bits(r.bits() | D4));
+                }
+
+                portb_regs.portb.modify(|r, w| w.bits(r.bits() | D12));
+            }
+        })
+    }
+}
+
+#[cfg(feature = "clock")]
+impl<P1: PortLayout, P2: PortLayout> MotorShield<ArduinoUno, P1, P2> {
+    // Starts a `Clock` on the overflow interrupt of the timer the `clock`
+    // feature picks: Timer2 (port 1), or Timer0 (port 2) with `clock-timer0`.
+    // `None` when the layout leaves that port empty.  The clock ticks once per
+    // PWM period of the port, 1024 us at 1 kHz down to 16 us at 64 kHz, where
+    // the interrupt alone takes a large share of the CPU.  Restarting it
+    // resets the count.
+    pub fn start_clock(&mut self) -> Option<Clock> {
+        // 256 counts per period at 16 counts per us before prescaling.
+        self.timers.clock_prescaler.map(|prescaler| Clock::start(16 * prescaler))
+    }
+}
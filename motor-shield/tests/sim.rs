@@ -0,0 +1,1337 @@
+use embedded_hal::{delay::DelayNs, digital::{ErrorType, InputPin, PinState}};
+use motor_shield::{
+    Easing, Empty, HomingConfig, HomingError, Keyframe, MicrostepCurve, MicrostepResolution, MotorCommands, MotorId,
+    MotorPort, MotorShield, Port1Frequency, Port2Frequency, ServoId, ServoSequence, ShieldError, ShieldLayout,
+    SimBoard, SimChannel, SimEvent, SimPwm, SimShield, SimShift, SingleStepper, StepperConfig, StepperDirection,
+    StepperHold, StepperId, StepperStyle, StopMode, Trace, TwoMotors, CURVE_POINTS, SINE_CURVE,
+};
+
+// round(255 * sin(i * 90 / 16 degrees)).
+const CURVE: [u16; 17] = [0, 25, 50, 74, 98, 120, 142, 162, 180, 197, 212, 225, 236, 244, 250, 254, 255];
+
+const STEPPER: MotorPort = MotorPort::SingleStepper(StepperConfig::new(48));
+
+// Stepper 1 coil bits on the latch.
+const A: u8 = 1 << 2;
+const B: u8 = 1 << 1;
+const C: u8 = 1 << 3;
+const D: u8 = 1 << 4;
+
+fn shield(port1: MotorPort, port2: MotorPort) -> (SimShield, Trace) {
+    let trace = Trace::new();
+    let layout = ShieldLayout {
+        port1,
+        port2,
+        frequency1: Port1Frequency::default(),
+        frequency2: Port2Frequency::default(),
+        servo1: true,
+        servo2: true,
+    };
+    let shield = SimShield::simulated(layout, &trace);
+
+    trace.clear();
+    (shield, trace)
+}
+
+fn coils(style: StepperStyle, dir: StepperDirection, steps: usize) -> Vec<u8> {
+    let (mut shield, trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    for _ in 0..steps {
+        stepper.onestep(dir, style);
+    }
+
+    trace.latched()
+}
+
+#[test]
+fn construction_latches_zero_then_enables_outputs() {
+    let trace = Trace::new();
+    let layout = ShieldLayout {
+        port1: MotorPort::TwoMotors,
+        port2: MotorPort::TwoMotors,
+        frequency1: Port1Frequency::Khz64,
+        frequency2: Port2Frequency::Khz8,
+        servo1: true,
+        servo2: true,
+    };
+    let _shield = SimShield::simulated(layout, &trace);
+
+    assert_eq!(trace.events(), [SimEvent::Latch(0), SimEvent::OutputsEnabled]);
+}
+
+#[test]
+fn timestamps_increase_with_every_event() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::Empty);
+    let mut motor = shield.motor(MotorId::M1).unwrap();
+
+    motor.enable();
+    motor.speed(128);
+    motor.run(MotorCommands::FORWARD);
+    motor.disable();
+
+    let records = trace.records();
+    assert_eq!(records.len(), 4);
+    assert!(records.windows(2).all(|w| w[0].at < w[1].at));
+    assert_eq!(
+        records.iter().map(|r| r.event).collect::<Vec<_>>(),
+        [
+            SimEvent::Enable(SimChannel::D11),
+            SimEvent::Duty(SimChannel::D11, 128),
+            SimEvent::Latch(A),
+            SimEvent::Disable(SimChannel::D11),
+        ]
+    );
+}
+
+#[test]
+fn single_forward() {
+    assert_eq!(coils(StepperStyle::SINGLE, StepperDirection::FORWARD, 8), [B, C, D, A, B, C, D, A]);
+}
+
+#[test]
+fn single_backward() {
+    assert_eq!(coils(StepperStyle::SINGLE, StepperDirection::BACKWARD, 8), [D, C, B, A, D, C, B, A]);
+}
+
+#[test]
+fn double_forward() {
+    assert_eq!(
+        coils(StepperStyle::DOUBLE, StepperDirection::FORWARD, 8),
+        [A | B, B | C, C | D, D | A, A | B, B | C, C | D, D | A]
+    );
+}
+
+#[test]
+fn double_backward() {
+    assert_eq!(
+        coils(StepperStyle::DOUBLE, StepperDirection::BACKWARD, 8),
+        [D | A, C | D, B | C, A | B, D | A, C | D, B | C, A | B]
+    );
+}
+
+#[test]
+fn interleave_forward() {
+    assert_eq!(
+        coils(StepperStyle::INTERLEAVE, StepperDirection::FORWARD, 8),
+        [A | B, B, B | C, C, C | D, D, D | A, A]
+    );
+}
+
+#[test]
+fn interleave_backward() {
+    assert_eq!(
+        coils(StepperStyle::INTERLEAVE, StepperDirection::BACKWARD, 8),
+        [D | A, D, C | D, C, B | C, B, A | B, A]
+    );
+}
+
+// Expected (coils, D11 duty, D3 duty) after moving to microstep `step` of the
+// 64-step electrical cycle.
+fn microstep(step: usize) -> (u8, u16, u16) {
+    match step / 16 {
+        0 => (A | B, CURVE[16 - step], CURVE[step]),
+        1 => (B | C, CURVE[step - 16], CURVE[32 - step]),
+        2 => (C | D, CURVE[48 - step], CURVE[step - 32]),
+        _ => (D | A, CURVE[step - 48], CURVE[64 - step]),
+    }
+}
+
+fn check_microsteps(dir: StepperDirection, positions: impl Iterator<Item = usize>) {
+    let (mut shield, trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    for step in positions {
+        trace.clear();
+        assert_eq!(stepper.onestep(dir, StepperStyle::MICROSTEP) as usize, step);
+
+        let (coils, duty_a, duty_b) = microstep(step);
+        assert_eq!(trace.latched(), [coils], "coils at microstep {}", step);
+        assert_eq!(trace.duties(SimChannel::D11), [duty_a], "coil A duty at microstep {}", step);
+        assert_eq!(trace.duties(SimChannel::D3), [duty_b], "coil B duty at microstep {}", step);
+    }
+}
+
+#[test]
+fn microstep_forward() {
+    check_microsteps(StepperDirection::FORWARD, (1..64).chain(0..2));
+}
+
+#[test]
+fn microstep_backward() {
+    check_microsteps(StepperDirection::BACKWARD, (0..64).rev().chain([63]));
+}
+
+#[test]
+fn stepper2_uses_its_own_coil_bits() {
+    let (mut shield, trace) = shield(MotorPort::Empty, STEPPER);
+    let mut stepper = shield.stepper(StepperId::S2).unwrap();
+
+    for _ in 0..4 {
+        stepper.onestep(StepperDirection::FORWARD, StepperStyle::SINGLE);
+    }
+
+    // STEPPER2_B, STEPPER2_C, STEPPER2_D, STEPPER2_A
+    assert_eq!(trace.latched(), [1 << 0, 1 << 7, 1 << 6, 1 << 5]);
+}
+
+#[test]
+fn motor_run_sets_direction_bits() {
+    // (motor, A bit, B bit)
+    let motors = [
+        (MotorId::M1, 1 << 2, 1 << 3),
+        (MotorId::M2, 1 << 1, 1 << 4),
+        (MotorId::M3, 1 << 5, 1 << 7),
+        (MotorId::M4, 1 << 0, 1 << 6),
+    ];
+
+    for (id, a, b) in motors {
+        let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::TwoMotors);
+        let mut motor = shield.motor(id).unwrap();
+
+        motor.run(MotorCommands::FORWARD);
+        motor.run(MotorCommands::BACKWARD);
+        motor.run(MotorCommands::RELEASE);
+
+        assert_eq!(trace.latched(), [a, b, 0], "motor {:?}", id);
+    }
+}
+
+#[test]
+fn motors_share_one_latch_byte() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::TwoMotors);
+
+    shield.motor(MotorId::M1).unwrap().run(MotorCommands::FORWARD);
+    shield.motor(MotorId::M3).unwrap().run(MotorCommands::BACKWARD);
+    shield.motor(MotorId::M4).unwrap().run(MotorCommands::FORWARD);
+    shield.motor(MotorId::M1).unwrap().run(MotorCommands::RELEASE);
+
+    assert_eq!(
+        trace.latched(),
+        [1 << 2, 1 << 2 | 1 << 7, 1 << 2 | 1 << 7 | 1 << 0, 1 << 7 | 1 << 0]
+    );
+}
+
+#[test]
+fn motor_and_stepper_share_one_latch_byte() {
+    let (mut shield, trace) = shield(STEPPER, MotorPort::TwoMotors);
+
+    shield.motor(MotorId::M3).unwrap().run(MotorCommands::FORWARD);
+    shield.stepper(StepperId::S1).unwrap().onestep(StepperDirection::FORWARD, StepperStyle::SINGLE);
+    shield.stepper(StepperId::S1).unwrap().release();
+
+    assert_eq!(trace.latched(), [1 << 5, 1 << 5 | B, 1 << 5]);
+}
+
+#[test]
+fn batch_latches_once() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::TwoMotors);
+
+    shield.batch(|tx| {
+        for id in MotorId::ALL {
+            tx.motor(id).unwrap().run(MotorCommands::BACKWARD);
+        }
+    });
+
+    assert_eq!(trace.latched(), [1 << 3 | 1 << 4 | 1 << 7 | 1 << 6]);
+}
+
+#[test]
+fn batch_skips_unchanged_byte() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::Empty);
+
+    shield.motor(MotorId::M1).unwrap().run(MotorCommands::FORWARD);
+    trace.clear();
+
+    shield.batch(|tx| {
+        tx.motor(MotorId::M1).unwrap().run(MotorCommands::BACKWARD);
+        tx.motor(MotorId::M1).unwrap().run(MotorCommands::FORWARD);
+    });
+
+    assert_eq!(trace.latched(), []);
+}
+
+#[test]
+fn nested_batch_latches_at_outermost_commit() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, STEPPER);
+
+    shield.batch(|tx| {
+        tx.motor(MotorId::M2).unwrap().run(MotorCommands::FORWARD);
+        tx.batch(|tx| {
+            tx.stepper(StepperId::S2).unwrap().onestep(StepperDirection::FORWARD, StepperStyle::DOUBLE);
+        });
+        assert_eq!(trace.latched(), []);
+    });
+
+    // MOTOR2_A, STEPPER2_A | STEPPER2_C
+    assert_eq!(trace.latched(), [1 << 1 | 1 << 5 | 1 << 0]);
+}
+
+#[test]
+fn batch_does_not_stage_duties() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::Empty);
+
+    shield.batch(|tx| {
+        tx.motor(MotorId::M1).unwrap().speed(200);
+        assert_eq!(trace.duties(SimChannel::D11), [200]);
+    });
+}
+
+#[test]
+fn unconfigured_motor_is_an_error_not_a_halt() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, STEPPER);
+
+    assert!(shield.motor(MotorId::M3).is_none());
+    assert_eq!(
+        shield.enable_motors(&[MotorId::M1, MotorId::M3]),
+        Err(ShieldError::MotorNotConfigured(MotorId::M3))
+    );
+    assert_eq!(
+        shield.set_speeds(&[(MotorId::M2, 100), (MotorId::M4, 100)]),
+        Err(ShieldError::MotorNotConfigured(MotorId::M4))
+    );
+
+    // A rejected request leaves every output untouched.
+    assert_eq!(trace.events(), []);
+
+    assert_eq!(shield.set_speeds(&[(MotorId::M1, 255), (MotorId::M2, 0)]), Ok(()));
+    assert_eq!(trace.duties(SimChannel::D11), [255]);
+    assert_eq!(trace.duties(SimChannel::D3), [0]);
+}
+
+#[test]
+fn set_velocity_sets_direction_and_duty() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::Empty);
+    let mut motor = shield.motor(MotorId::M1).unwrap();
+
+    motor.set_velocity(200);
+    assert_eq!(motor.velocity(), 200);
+    motor.set_velocity(-100);
+    assert_eq!(motor.velocity(), -100);
+    motor.set_velocity(0);
+    assert_eq!(motor.velocity(), 0);
+
+    assert_eq!(
+        trace.events(),
+        [
+            SimEvent::Latch(A),
+            SimEvent::Duty(SimChannel::D11, 200),
+            // Reversal: duty drops before the bridge flips.
+            SimEvent::Duty(SimChannel::D11, 0),
+            SimEvent::Latch(1 << 3),
+            SimEvent::Duty(SimChannel::D11, 100),
+            SimEvent::Duty(SimChannel::D11, 0),
+            SimEvent::Latch(0),
+            SimEvent::Duty(SimChannel::D11, 0),
+        ]
+    );
+}
+
+#[test]
+fn set_velocity_clamps() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::Empty);
+    let mut motor = shield.motor(MotorId::M2).unwrap();
+
+    motor.set_velocity(i16::MIN);
+    assert_eq!(motor.velocity(), -255);
+    assert_eq!(trace.duties(SimChannel::D3), [255]);
+}
+
+#[test]
+fn set_velocities_latches_once() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::TwoMotors);
+
+    shield.set_velocities(&[(MotorId::M1, 100), (MotorId::M3, 100)]).unwrap();
+    trace.clear();
+
+    shield
+        .set_velocities(&[(MotorId::M1, -50), (MotorId::M3, 80), (MotorId::M4, -255)])
+        .unwrap();
+
+    assert_eq!(
+        trace.events(),
+        [
+            SimEvent::Duty(SimChannel::D11, 0),
+            SimEvent::Latch(1 << 3 | 1 << 5 | 1 << 6),
+            SimEvent::Duty(SimChannel::D11, 50),
+            SimEvent::Duty(SimChannel::D6, 80),
+            SimEvent::Duty(SimChannel::D5, 255),
+        ]
+    );
+}
+
+#[test]
+fn brake_sets_both_bridge_inputs() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::Empty);
+    let mut motor = shield.motor(MotorId::M2).unwrap();
+
+    motor.set_velocity(120);
+    trace.clear();
+    motor.stop(StopMode::Brake);
+
+    assert_eq!(motor.velocity(), 0);
+    assert_eq!(
+        trace.events(),
+        [SimEvent::Latch(1 << 1 | 1 << 4), SimEvent::Duty(SimChannel::D3, 255)]
+    );
+}
+
+#[test]
+fn stop_all_brakes_every_motor_with_one_latch() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::SingleMotorFirst);
+
+    shield.set_velocities(&[(MotorId::M1, 200), (MotorId::M2, -200), (MotorId::M3, 50)]).unwrap();
+    trace.clear();
+    shield.stop_all(StopMode::Brake);
+
+    assert_eq!(
+        trace.events(),
+        [
+            SimEvent::Latch(1 << 2 | 1 << 3 | 1 << 1 | 1 << 4 | 1 << 5 | 1 << 7),
+            SimEvent::Duty(SimChannel::D11, 255),
+            SimEvent::Duty(SimChannel::D3, 255),
+            SimEvent::Duty(SimChannel::D6, 255),
+        ]
+    );
+}
+
+#[test]
+fn stop_all_coasts_after_dropping_duty() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::Empty);
+
+    shield.set_velocities(&[(MotorId::M1, 200), (MotorId::M2, -200)]).unwrap();
+    trace.clear();
+    shield.stop_all(StopMode::Coast);
+
+    assert_eq!(
+        trace.events(),
+        [
+            SimEvent::Duty(SimChannel::D11, 0),
+            SimEvent::Duty(SimChannel::D3, 0),
+            SimEvent::Latch(0),
+        ]
+    );
+}
+
+#[test]
+fn velocity_after_brake_restarts_from_zero_duty() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::Empty);
+    let mut motor = shield.motor(MotorId::M1).unwrap();
+
+    motor.stop(StopMode::Brake);
+    trace.clear();
+    motor.set_velocity(-80);
+
+    assert_eq!(
+        trace.events(),
+        [
+            SimEvent::Duty(SimChannel::D11, 0),
+            SimEvent::Latch(1 << 3),
+            SimEvent::Duty(SimChannel::D11, 80),
+        ]
+    );
+}
+
+#[test]
+fn update_ramps_towards_target() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::Empty);
+    let mut motor = shield.motor(MotorId::M1).unwrap();
+
+    motor.set_acceleration(Some(100));
+    motor.set_velocity(255);
+    assert_eq!((motor.velocity(), motor.target_velocity()), (0, 255));
+    assert!(trace.events().is_empty());
+
+    for _ in 0..4 {
+        shield.update(1);
+    }
+
+    assert_eq!(trace.latched(), [1 << 2]);
+    assert_eq!(trace.duties(SimChannel::D11), [100, 200, 255]);
+    assert_eq!(shield.motor(MotorId::M1).unwrap().velocity(), 255);
+}
+
+#[test]
+fn zero_acceleration_means_no_limit() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::Empty);
+    let mut motor = shield.motor(MotorId::M1).unwrap();
+
+    motor.set_acceleration(Some(0));
+    assert_eq!(motor.acceleration(), None);
+
+    motor.set_velocity(120);
+    assert_eq!(motor.velocity(), 120);
+
+    shield.update(1);
+    assert_eq!(trace.duties(SimChannel::D11), [120]);
+}
+
+#[test]
+fn update_scales_with_dt() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::Empty);
+
+    shield.motor(MotorId::M2).unwrap().set_acceleration(Some(10));
+    shield.set_velocities(&[(MotorId::M2, -100)]).unwrap();
+    shield.update(3);
+    shield.update(20);
+
+    assert_eq!(trace.duties(SimChannel::D3), [30, 100]);
+    assert_eq!(shield.motor(MotorId::M2).unwrap().velocity(), -100);
+}
+
+#[test]
+fn ramped_reversal_stops_at_zero_before_flipping() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::Empty);
+    let mut motor = shield.motor(MotorId::M1).unwrap();
+
+    motor.set_velocity(255);
+    motor.set_acceleration(Some(200));
+    motor.set_velocity(-255);
+    trace.clear();
+
+    let mut velocities = Vec::new();
+    for _ in 0..4 {
+        shield.update(1);
+        velocities.push(shield.motor(MotorId::M1).unwrap().velocity());
+    }
+
+    assert_eq!(velocities, [55, 0, -200, -255]);
+    assert_eq!(
+        trace.events(),
+        [
+            SimEvent::Duty(SimChannel::D11, 55),
+            SimEvent::Duty(SimChannel::D11, 0),
+            SimEvent::Latch(0),
+            SimEvent::Duty(SimChannel::D11, 0),
+            SimEvent::Latch(1 << 3),
+            SimEvent::Duty(SimChannel::D11, 200),
+            SimEvent::Duty(SimChannel::D11, 255),
+        ]
+    );
+}
+
+#[test]
+fn update_leaves_unramped_and_braked_motors_alone() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, MotorPort::Empty);
+
+    shield.motor(MotorId::M1).unwrap().set_velocity(120);
+    let mut motor = shield.motor(MotorId::M2).unwrap();
+    motor.set_acceleration(Some(50));
+    motor.stop(StopMode::Brake);
+    trace.clear();
+
+    shield.update(5);
+
+    assert!(trace.events().is_empty());
+}
+
+#[test]
+fn poll_steps_when_due() {
+    let (mut shield, trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    // 48 steps at 250 rpm: 5000us per step.
+    stepper.set_speed(250);
+    stepper.move_relative(3, StepperDirection::FORWARD, StepperStyle::SINGLE);
+
+    let polls = [(0, true), (4999, true), (5000, true), (9000, true), (10000, false), (20000, false)];
+    for (now, moving) in polls {
+        assert_eq!(stepper.poll(now), moving, "at {now}us");
+    }
+
+    assert!(!stepper.is_moving());
+    assert_eq!(trace.latched(), [B, C, D]);
+}
+
+#[test]
+fn poll_survives_clock_wrap() {
+    let (mut shield, trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    stepper.set_speed(250);
+    stepper.move_relative(2, StepperDirection::BACKWARD, StepperStyle::SINGLE);
+
+    assert!(stepper.poll(u32::MAX - 1000));
+    assert!(stepper.poll(3000));
+    assert!(!stepper.poll(4000));
+    assert_eq!(trace.latched(), [D, C]);
+}
+
+#[test]
+fn polled_microstep_move_ends_on_a_full_step() {
+    let (mut shield, _trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    stepper.onestep(StepperDirection::FORWARD, StepperStyle::MICROSTEP);
+    stepper.move_relative(1, StepperDirection::FORWARD, StepperStyle::MICROSTEP);
+
+    let mut polls = 0;
+    while stepper.poll(0) {
+        polls += 1;
+    }
+
+    assert_eq!(polls + 1, 31);
+    assert_eq!(stepper.onestep(StepperDirection::BACKWARD, StepperStyle::MICROSTEP), 31);
+}
+
+#[test]
+fn poll_steppers_latches_both_steps_together() {
+    let (mut shield, trace) = shield(STEPPER, STEPPER);
+
+    for id in StepperId::ALL {
+        let mut stepper = shield.stepper(id).unwrap();
+        stepper.set_speed(250);
+        stepper.move_relative(2, StepperDirection::FORWARD, StepperStyle::SINGLE);
+    }
+
+    assert!(shield.poll_steppers(0));
+    assert!(shield.poll_steppers(2500));
+    assert!(!shield.poll_steppers(5000));
+    assert!(!shield.poll_steppers(10000));
+
+    assert_eq!(trace.latched(), [B | 1 << 0, C | 1 << 7]);
+}
+
+// Records every wait instead of sleeping.
+#[derive(Default)]
+struct Waits(Vec<u32>);
+
+impl DelayNs for Waits {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.push(ns / 1000);
+    }
+}
+
+fn ramped_waits(steps: u32) -> Vec<u32> {
+    let (mut shield, trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+    let mut waits = Waits::default();
+
+    stepper.set_max_speed(1000);
+    stepper.set_acceleration(2000);
+    stepper.step(steps, StepperDirection::FORWARD, StepperStyle::DOUBLE, &mut waits);
+
+    assert_eq!(trace.latched().len(), steps as usize);
+    waits.0
+}
+
+#[test]
+fn ramped_move_speeds_up_cruises_and_slows_down() {
+    let waits = ramped_waits(1000);
+    let fastest = waits.iter().position(|&w| w == 1000).unwrap();
+    let slowing = waits.iter().rposition(|&w| w == 1000).unwrap();
+
+    assert_eq!(waits.len(), 999);
+    // 0.676 * sqrt(2 / 2000) s, then 1000 steps/s after v^2 / 2a = 250 steps.
+    assert_eq!(waits[0], 21376);
+    assert!((245..=255).contains(&fastest), "cruising from step {fastest}");
+    assert!((740..=755).contains(&slowing), "slowing from step {slowing}");
+    assert!(waits[..fastest].windows(2).all(|w| w[0] > w[1]));
+    assert!(waits[slowing..].windows(2).all(|w| w[0] <= w[1]));
+    assert!(waits[998] > 10000);
+}
+
+#[test]
+fn short_ramped_move_never_reaches_cruise() {
+    let waits = ramped_waits(100);
+    let fastest = waits.iter().min().unwrap();
+
+    assert!(*fastest > 1000);
+    assert!(waits[0] > waits[50] && waits[98] > waits[50]);
+}
+
+#[test]
+fn microstep_step_ends_alike_with_and_without_ramp() {
+    let end = |acceleration: u16, offset: usize, dir: StepperDirection| {
+        let (mut shield, _trace) = shield(STEPPER, MotorPort::Empty);
+        let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+        for _ in 0..offset {
+            stepper.onestep(StepperDirection::FORWARD, StepperStyle::MICROSTEP);
+        }
+
+        stepper.set_max_speed(1000);
+        stepper.set_acceleration(acceleration);
+        stepper.step(3, dir, StepperStyle::MICROSTEP, &mut Waits::default());
+        stepper.current_position()
+    };
+
+    assert_eq!(end(0, 0, StepperDirection::FORWARD), 3 * 16);
+
+    for offset in [0, 5, 16, 37] {
+        for dir in [StepperDirection::FORWARD, StepperDirection::BACKWARD] {
+            assert_eq!(end(0, offset, dir), end(2000, offset, dir), "offset {}", offset);
+        }
+    }
+}
+
+#[test]
+fn polled_ramp_matches_blocking_ramp() {
+    let (mut shield, trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    stepper.set_max_speed(1000);
+    stepper.set_acceleration(2000);
+    stepper.move_relative(1000, StepperDirection::FORWARD, StepperStyle::DOUBLE);
+
+    // Each step is due exactly when the blocking move would have taken it.
+    let mut now = 0;
+    assert!(stepper.poll(now));
+    let waits = ramped_waits(1000);
+
+    for (i, &wait) in waits.iter().enumerate() {
+        assert!(stepper.poll(now + wait - 1));
+        assert_eq!(trace.latched().len(), i + 1);
+        now += wait;
+        assert_eq!(stepper.poll(now), i + 1 < waits.len());
+    }
+
+    assert_eq!(trace.latched().len(), 1000);
+}
+
+// Polls a move to completion, a second apart so every step is due.
+fn run_to_end(stepper: &mut motor_shield::Stepper<'_, motor_shield::SimPwm, motor_shield::SimShift>) -> u32 {
+    let mut now = 0u32;
+
+    while stepper.poll(now) {
+        now = now.wrapping_add(1_000_000);
+    }
+
+    now / 1_000_000 + 1
+}
+
+#[test]
+fn position_counts_microsteps_in_every_style() {
+    let (mut shield, _trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    stepper.onestep(StepperDirection::FORWARD, StepperStyle::SINGLE);
+    assert_eq!(stepper.current_position(), 16);
+    stepper.onestep(StepperDirection::FORWARD, StepperStyle::INTERLEAVE);
+    assert_eq!(stepper.current_position(), 24);
+    stepper.onestep(StepperDirection::FORWARD, StepperStyle::SINGLE);
+    assert_eq!(stepper.current_position(), 32);
+    stepper.onestep(StepperDirection::BACKWARD, StepperStyle::MICROSTEP);
+    assert_eq!(stepper.current_position(), 31);
+
+    for _ in 0..200 {
+        stepper.onestep(StepperDirection::BACKWARD, StepperStyle::DOUBLE);
+    }
+    assert_eq!(stepper.current_position(), 31 - 200 * 16);
+}
+
+#[test]
+fn move_to_reaches_the_target() {
+    let (mut shield, trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    stepper.move_to(-5 * 16);
+    assert_eq!(stepper.distance_to_go(), -80);
+    assert_eq!(run_to_end(&mut stepper), 5);
+    assert_eq!(stepper.current_position(), -80);
+    assert_eq!(stepper.distance_to_go(), 0);
+    assert_eq!(trace.latched(), [D, C, B, A, D]);
+
+    // Between two full steps: stops at the last one short of it.
+    stepper.move_to(-80 + 40);
+    run_to_end(&mut stepper);
+    assert_eq!(stepper.current_position(), -48);
+
+    stepper.set_style(StepperStyle::MICROSTEP);
+    stepper.move_to(-48 + 3);
+    run_to_end(&mut stepper);
+    assert_eq!(stepper.current_position(), -45);
+}
+
+#[test]
+fn set_current_position_rebases_moves() {
+    let (mut shield, _trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    stepper.set_style(StepperStyle::DOUBLE);
+    stepper.move_to(160);
+    stepper.set_current_position(1000);
+    assert!(!stepper.is_moving());
+
+    stepper.move_to(1000 + 8 + 16);
+    assert_eq!(run_to_end(&mut stepper), 2);
+    assert_eq!(stepper.current_position(), 1024);
+}
+
+#[test]
+fn moves_past_the_position_range_are_clamped() {
+    let (mut shield, _trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    stepper.move_relative(200_000_000, StepperDirection::FORWARD, StepperStyle::MICROSTEP);
+    assert_eq!(stepper.target_position(), i32::MAX);
+
+    stepper.set_current_position(-10);
+    stepper.move_to(i32::MAX);
+    assert_eq!(stepper.distance_to_go(), i32::MAX);
+
+    stepper.set_current_position(i32::MIN + 5);
+    stepper.move_relative(u32::MAX, StepperDirection::BACKWARD, StepperStyle::MICROSTEP);
+    assert_eq!(stepper.target_position(), i32::MIN);
+    assert!(stepper.poll(0));
+}
+
+#[test]
+fn stop_decelerates_a_ramped_move() {
+    let (mut shield, _trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    stepper.set_max_speed(1000);
+    stepper.set_acceleration(2000);
+    stepper.move_to(16_000);
+
+    let mut now = 0u32;
+    for _ in 0..400 {
+        stepper.poll(now);
+        now += 25_000;
+    }
+    assert_eq!(stepper.current_position(), 400 * 16);
+
+    stepper.stop();
+    // v^2 / 2a = 250 steps at full speed.
+    assert!((245 * 16..=255 * 16).contains(&stepper.distance_to_go()));
+
+    while stepper.poll(now) {
+        now += 25_000;
+    }
+    assert!((645 * 16..=655 * 16).contains(&stepper.current_position()));
+    assert!(!stepper.is_moving());
+}
+
+#[test]
+fn ramped_move_turns_round_for_a_target_behind() {
+    let (mut shield, _trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    stepper.set_max_speed(1000);
+    stepper.set_acceleration(2000);
+    stepper.move_to(16_000);
+
+    let mut now = 0u32;
+    for _ in 0..400 {
+        stepper.poll(now);
+        now += 25_000;
+    }
+
+    stepper.move_to(0);
+
+    let mut furthest = stepper.current_position();
+    while stepper.poll(now) {
+        furthest = furthest.max(stepper.current_position());
+        now += 25_000;
+    }
+
+    assert!(furthest > 640 * 16, "overshot to {furthest}");
+    assert_eq!(stepper.current_position(), 0);
+}
+
+// A limit switch pulled low once stepper 1 is at or below `at` full steps,
+// found by replaying the SINGLE-step coils latched so far.
+struct Switch {
+    trace: Trace,
+    at: i32,
+}
+
+impl Switch {
+    fn position(&self) -> i32 {
+        let phase = |byte| [A, B, C, D].iter().position(|&coil| coil == byte).unwrap() as i32;
+
+        self.trace.latched().into_iter().fold((0, 0), |(position, last), byte| {
+            let next = phase(byte);
+            (position + (next - last + 5) % 4 - 1, next)
+        }).0
+    }
+}
+
+impl ErrorType for Switch {
+    type Error = core::convert::Infallible;
+}
+
+impl InputPin for Switch {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.position() > self.at)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+const HOMING: HomingConfig = HomingConfig {
+    dir: StepperDirection::BACKWARD,
+    style: StepperStyle::SINGLE,
+    triggered: PinState::Low,
+    speed: 200,
+    slow_speed: 50,
+    backoff: 5,
+    max_travel: 100,
+};
+
+#[test]
+fn homing_zeroes_at_the_switch() {
+    let (mut shield, trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+    let mut switch = Switch { trace: trace.clone(), at: -30 };
+    let mut waits = Waits::default();
+
+    stepper.set_current_position(1234);
+    stepper.home(&mut switch, &HOMING, &mut waits).unwrap();
+
+    assert_eq!(stepper.current_position(), 0);
+    assert_eq!(switch.position(), -30);
+    // 30 steps in, 5 out and 5 back in.
+    assert_eq!(trace.latched().len(), 40);
+    assert_eq!(waits.0.iter().filter(|&&w| w == 5000).count(), 35);
+    assert_eq!(waits.0.iter().filter(|&&w| w == 20000).count(), 5);
+}
+
+#[test]
+fn homing_fails_on_a_pressed_switch() {
+    let (mut shield, trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+    let mut switch = Switch { trace: trace.clone(), at: 0 };
+
+    assert_eq!(
+        stepper.home(&mut switch, &HOMING, &mut Waits::default()),
+        Err(HomingError::AlreadyTriggered)
+    );
+    assert!(trace.latched().is_empty());
+}
+
+#[test]
+fn homing_gives_up_after_max_travel() {
+    let (mut shield, trace) = shield(STEPPER, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+    let mut switch = Switch { trace: trace.clone(), at: -500 };
+
+    assert_eq!(
+        stepper.home(&mut switch, &HOMING, &mut Waits::default()),
+        Err(HomingError::TravelExceeded)
+    );
+    assert_eq!(switch.position(), -100);
+    assert_eq!(stepper.current_position(), -1600);
+}
+
+#[test]
+fn linear_move_interleaves_both_axes() {
+    let (mut shield, trace) = shield(STEPPER, STEPPER);
+    let mut waits = Waits::default();
+
+    shield.move_steppers_linear(10, -4, 100, StepperStyle::SINGLE, &mut waits).unwrap();
+
+    assert_eq!(shield.stepper(StepperId::S1).unwrap().current_position(), 160);
+    assert_eq!(shield.stepper(StepperId::S2).unwrap().current_position(), -64);
+    assert_eq!(waits.0, [10_000; 10]);
+
+    // One latch per tick; stepper 2 steps on every second or third of them.
+    let s2: Vec<u8> = trace.latched().iter().map(|byte| byte & (1 << 5 | 1 << 0 | 1 << 7 | 1 << 6)).collect();
+    let ticks: Vec<usize> = (0..10).filter(|&i| s2[i] != if i == 0 { 0 } else { s2[i - 1] }).collect();
+
+    assert_eq!(s2.len(), 10);
+    assert_eq!(ticks, [1, 3, 6, 8]);
+}
+
+#[test]
+fn linear_move_needs_the_steppers_it_moves() {
+    let (mut shield, trace) = shield(STEPPER, MotorPort::TwoMotors);
+    let mut waits = Waits::default();
+
+    assert_eq!(
+        shield.move_steppers_linear(3, 1, 100, StepperStyle::SINGLE, &mut waits),
+        Err(ShieldError::StepperNotConfigured(StepperId::S2))
+    );
+    assert!(trace.latched().is_empty());
+
+    shield.move_steppers_linear(0, 0, 100, StepperStyle::SINGLE, &mut waits).unwrap();
+    shield.move_steppers_linear(-3, 0, 100, StepperStyle::MICROSTEP, &mut waits).unwrap();
+    assert_eq!(shield.stepper(StepperId::S1).unwrap().current_position(), -48);
+}
+
+#[test]
+fn set_speed_handles_large_step_counts() {
+    let port = MotorPort::SingleStepper(StepperConfig::new(200));
+    let (mut shield, trace) = shield(port, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    // 200 * 400 overflows a u16: 750us per step.
+    stepper.set_speed(400);
+    stepper.move_relative(2, StepperDirection::FORWARD, StepperStyle::SINGLE);
+
+    assert!(stepper.poll(0));
+    assert!(stepper.poll(749));
+    assert_eq!(trace.latched().len(), 1);
+    assert!(!stepper.poll(750));
+}
+
+#[test]
+fn configured_style_and_hold_apply_to_moves() {
+    let port = MotorPort::SingleStepper(StepperConfig {
+        style: StepperStyle::INTERLEAVE,
+        hold: StepperHold::RELEASE,
+        ..StepperConfig::new(2048)
+    });
+    let (mut shield, trace) = shield(STEPPER, port);
+    let mut stepper = shield.stepper(StepperId::S2).unwrap();
+
+    stepper.move_to(3 * 8);
+    run_to_end(&mut stepper);
+
+    assert_eq!(stepper.current_position(), 24);
+    assert_eq!(trace.latched(), [1 << 5 | 1 << 0, 1 << 0, 1 << 0 | 1 << 7, 0]);
+}
+
+#[test]
+fn sine_curve_matches_floats() {
+    for (i, &duty) in SINE_CURVE.iter().enumerate() {
+        let expected = (255.0 * (i as f64 / CURVE_POINTS as f64 * core::f64::consts::FRAC_PI_2).sin()).round();
+        assert_eq!(duty as f64, expected, "point {i}");
+    }
+}
+
+// Signed winding currents after each of `steps` microsteps: coils A and C
+// are the two ends of the winding on D11, B and D of the one on D3.
+fn winding_currents(config: StepperConfig, dir: StepperDirection, steps: usize) -> Vec<(i32, i32)> {
+    let (mut shield, trace) = shield(MotorPort::SingleStepper(config), MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    (0..steps)
+        .map(|_| {
+            trace.clear();
+            stepper.onestep(dir, StepperStyle::MICROSTEP);
+
+            let (coils, a, b) = (trace.latched()[0], trace.duties(SimChannel::D11)[0], trace.duties(SimChannel::D3)[0]);
+            let signed = |duty: u16, plus: u8, minus: u8| match (coils & plus != 0, coils & minus != 0) {
+                (true, false) => duty as i32,
+                (false, true) => -(duty as i32),
+                _ => panic!("winding driven both ways: {coils:#010b}"),
+            };
+
+            (signed(a, A, C), signed(b, B, D))
+        })
+        .collect()
+}
+
+#[test]
+fn microstep_phase_is_continuous_at_every_resolution() {
+    use MicrostepResolution::*;
+
+    for resolution in [X8, X16, X32, X64] {
+        let microsteps = resolution.microsteps() as usize;
+        let config = StepperConfig { microsteps: resolution, ..StepperConfig::new(200) };
+        // Largest change of 255 * sin between neighbouring points, plus rounding.
+        let limit = (255.0 * core::f64::consts::FRAC_PI_2 / microsteps as f64).ceil() as i32 + 1;
+
+        for dir in [StepperDirection::FORWARD, StepperDirection::BACKWARD] {
+            let currents = winding_currents(config, dir, 4 * microsteps + 1);
+
+            assert_eq!(currents[0], currents[4 * microsteps], "{resolution:?} wraps");
+            for (i, pair) in currents.windows(2).enumerate() {
+                let ((a0, b0), (a1, b1)) = (pair[0], pair[1]);
+
+                assert!((a1 - a0).abs() <= limit && (b1 - b0).abs() <= limit, "{resolution:?} jumps at {i}: {pair:?}");
+                assert!((a1 * a1 + b1 * b1 - 255 * 255).abs() < 2 * 255 * 2, "{resolution:?} magnitude at {i}");
+            }
+        }
+    }
+}
+
+#[test]
+fn microstep_position_and_run_on_follow_the_resolution() {
+    let config = StepperConfig { microsteps: MicrostepResolution::X64, style: StepperStyle::MICROSTEP, ..StepperConfig::new(200) };
+    let (mut shield, _trace) = shield(MotorPort::SingleStepper(config), MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    assert_eq!(stepper.microsteps_per_step(), 64);
+    for _ in 0..200 {
+        stepper.onestep(StepperDirection::FORWARD, StepperStyle::MICROSTEP);
+    }
+    assert_eq!(stepper.current_position(), 200);
+
+    stepper.move_relative(1, StepperDirection::FORWARD, StepperStyle::MICROSTEP);
+    run_to_end(&mut stepper);
+    assert_eq!(stepper.current_position(), 320);
+
+    stepper.onestep(StepperDirection::FORWARD, StepperStyle::SINGLE);
+    assert_eq!(stepper.current_position(), 384);
+}
+
+#[test]
+fn custom_curve_replaces_the_sine() {
+    static LINEAR: [u8; CURVE_POINTS + 1] = {
+        let mut curve = [0; CURVE_POINTS + 1];
+        let mut i = 0;
+        while i <= CURVE_POINTS {
+            curve[i] = (i * 255 / CURVE_POINTS) as u8;
+            i += 1;
+        }
+        curve
+    };
+    let config = StepperConfig {
+        microsteps: MicrostepResolution::X8,
+        curve: MicrostepCurve::Custom(&LINEAR),
+        ..StepperConfig::new(200)
+    };
+
+    let currents = winding_currents(config, StepperDirection::FORWARD, 4);
+
+    assert_eq!(currents, [(223, 31), (191, 63), (159, 95), (127, 127)]);
+}
+
+#[test]
+fn idle_stepper_drops_to_hold_duty_then_releases() {
+    let hold = StepperHold { duty: 64, settle_ms: 10, release_ms: Some(100) };
+    let port = MotorPort::SingleStepper(StepperConfig { hold, ..StepperConfig::new(48) });
+    let (mut shield, trace) = shield(port, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    stepper.set_speed(250);
+    stepper.move_relative(2, StepperDirection::FORWARD, StepperStyle::SINGLE);
+    assert!(stepper.poll(0));
+    assert!(!stepper.poll(5000));
+    trace.clear();
+
+    stepper.poll(14_999);
+    assert!(trace.events().is_empty());
+    stepper.poll(15_000);
+    stepper.poll(16_000);
+    assert_eq!(trace.events(), [SimEvent::Duty(SimChannel::D11, 64), SimEvent::Duty(SimChannel::D3, 64)]);
+
+    trace.clear();
+    stepper.poll(104_999);
+    assert!(trace.events().is_empty());
+    stepper.poll(105_000);
+    stepper.poll(200_000);
+    assert_eq!(trace.events(), [SimEvent::Latch(0)]);
+
+    // The next step is back at full current.
+    trace.clear();
+    stepper.onestep(StepperDirection::FORWARD, StepperStyle::SINGLE);
+    assert_eq!(trace.duties(SimChannel::D11), [255]);
+}
+
+#[test]
+fn hold_duty_scales_microstep_currents() {
+    let hold = StepperHold { duty: 128, settle_ms: 0, release_ms: None };
+    let port = MotorPort::SingleStepper(StepperConfig { hold, ..StepperConfig::new(48) });
+    let (mut shield, trace) = shield(port, MotorPort::Empty);
+    let mut stepper = shield.stepper(StepperId::S1).unwrap();
+
+    // A blocking step leaves the still time to be counted from the next poll.
+    stepper.onestep(StepperDirection::FORWARD, StepperStyle::MICROSTEP);
+    trace.clear();
+    stepper.poll(1_000_000);
+    stepper.poll(u32::MAX);
+
+    assert_eq!(trace.duties(SimChannel::D11), [CURVE[15] * 128 / 255]);
+    assert_eq!(trace.duties(SimChannel::D3), [CURVE[1] * 128 / 255]);
+    assert!(trace.latched().is_empty());
+}
+
+#[test]
+fn servo_pulse_counts_half_microseconds() {
+    let (mut shield, trace) = shield(MotorPort::Empty, MotorPort::Empty);
+    let servo = shield.servo(ServoId::S1).unwrap();
+
+    servo.set_pulse_us(1500);
+    servo.set_pulse_us(25_000);
+
+    assert_eq!(trace.duties(SimChannel::D10), [2999, 39_999]);
+}
+
+#[test]
+fn servo_degrees_follow_calibration() {
+    let (mut shield, trace) = shield(MotorPort::Empty, MotorPort::Empty);
+
+    let s1 = shield.servo(ServoId::S1).unwrap();
+    s1.set_degrees(0);
+    s1.set_degrees(90);
+    s1.set_degrees(200);
+
+    let s2 = shield.servo(ServoId::S2).unwrap();
+    s2.set_calibration(500, 2500);
+    s2.set_degrees(90);
+    s2.set_degrees(180);
+
+    assert_eq!(trace.duties(SimChannel::D10), [1999, 2999, 3999]);
+    assert_eq!(trace.duties(SimChannel::D9), [2999, 4999]);
+}
+
+#[test]
+fn servo_move_to_eases_over_its_duration() {
+    let (mut shield, trace) = shield(MotorPort::Empty, MotorPort::Empty);
+    let servo = shield.servo(ServoId::S1).unwrap();
+
+    // Not positioned yet: jumps.
+    servo.move_to(0, 1000);
+    assert!(!servo.is_moving());
+
+    servo.move_to(180, 100);
+    assert!(servo.update(25));
+    assert!(servo.update(25));
+    assert!(!servo.update(60));
+    assert!(!servo.update(10));
+
+    servo.set_easing(Easing::EaseInOut);
+    servo.move_to(0, 100);
+    servo.update(25);
+    servo.update(25);
+    servo.update(50);
+
+    assert_eq!(trace.duties(SimChannel::D10), [1999, 2499, 2999, 3999, 3685, 2999, 1999]);
+}
+
+#[test]
+fn servo_sequence_moves_both_servos_together() {
+    const FRAMES: [Keyframe; 3] = [
+        Keyframe::new(Some(0), Some(180), 0, Easing::Linear),
+        Keyframe::new(Some(180), Some(0), 100, Easing::Linear),
+        Keyframe::new(None, None, 50, Easing::Linear),
+    ];
+
+    let (mut shield, _trace) = shield(MotorPort::Empty, MotorPort::Empty);
+    let mut sequence = ServoSequence::new(&FRAMES);
+    let pulses = |shield: &mut SimShield| ServoId::ALL.map(|id| shield.servo(id).unwrap().pulse_us().unwrap());
+
+    assert!(sequence.update(&mut shield, 0));
+    assert_eq!(pulses(&mut shield), [1000, 2000]);
+
+    assert!(sequence.update(&mut shield, 50));
+    assert_eq!(pulses(&mut shield), [1500, 1500]);
+
+    // Arrives 50 ms in, then pauses for the rest.
+    assert!(sequence.update(&mut shield, 99));
+    assert_eq!(pulses(&mut shield), [2000, 1000]);
+    assert!(!sequence.update(&mut shield, 1));
+    assert!(sequence.is_finished());
+
+    sequence.set_looping(true);
+    sequence.restart();
+    sequence.update(&mut shield, 0);
+    assert!(sequence.update(&mut shield, 175));
+    assert_eq!(pulses(&mut shield), [1250, 1750]);
+}
+
+#[test]
+fn absent_servos_are_not_built() {
+    let trace = Trace::new();
+    let layout = ShieldLayout {
+        port1: MotorPort::Empty,
+        port2: MotorPort::Empty,
+        frequency1: Port1Frequency::default(),
+        frequency2: Port2Frequency::default(),
+        servo1: false,
+        servo2: true,
+    };
+    let mut shield = SimShield::simulated(layout, &trace);
+
+    assert!(shield.servo(ServoId::S1).is_none());
+    shield.servo(ServoId::S2).unwrap().set_degrees(0);
+    assert!(!shield.update_servos(10));
+
+    assert_eq!(trace.duties(SimChannel::D9), [1999]);
+    assert!(trace.duties(SimChannel::D10).is_empty());
+}
+
+#[test]
+fn into_parts_releases_every_output() {
+    let (mut shield, trace) = shield(MotorPort::TwoMotors, STEPPER);
+
+    shield.enable_motors(&[MotorId::M1]).unwrap();
+    shield.set_velocities(&[(MotorId::M1, 200)]).unwrap();
+    shield.stepper(StepperId::S2).unwrap().onestep(StepperDirection::FORWARD, StepperStyle::SINGLE);
+    trace.clear();
+
+    let (_shift, (), motor_pwm, servo_pwm) = shield.into_parts();
+
+    assert!(motor_pwm.iter().all(Option::is_some));
+    assert!(servo_pwm.iter().all(Option::is_some));
+
+    let mut expected = Vec::new();
+    for channel in [SimChannel::D11, SimChannel::D3, SimChannel::D6, SimChannel::D5, SimChannel::D10, SimChannel::D9] {
+        expected.extend([SimEvent::Duty(channel, 0), SimEvent::Disable(channel)]);
+    }
+    expected.push(SimEvent::Latch(0));
+
+    assert_eq!(trace.events(), expected);
+}
+
+#[test]
+fn typed_layout_accessors_reach_their_outputs() {
+    let trace = Trace::new();
+    let layout = ShieldLayout {
+        port1: TwoMotors,
+        port2: SingleStepper(StepperConfig::new(48)),
+        frequency1: Port1Frequency::Khz8,
+        frequency2: Port2Frequency::Khz64,
+        servo1: false,
+        servo2: false,
+    };
+    let mut shield: MotorShield<SimBoard, TwoMotors, SingleStepper> = MotorShield::simulated(layout, &trace);
+    trace.clear();
+
+    shield.m2().set_velocity(-100);
+    shield.stepper2().onestep(StepperDirection::FORWARD, StepperStyle::SINGLE);
+
+    assert_eq!(trace.duties(SimChannel::D3), [100]);
+    // M2 backward, then the first stepper 2 coil.
+    assert_eq!(trace.latched(), [1 << 4, 1 << 4 | 1 << 0]);
+}
+
+#[test]
+fn from_parts_needs_the_channels_of_the_layout() {
+    let trace = Trace::new();
+    let layout = || ShieldLayout {
+        port1: TwoMotors,
+        port2: SingleStepper(StepperConfig::new(48)),
+        frequency1: Port1Frequency::default(),
+        frequency2: Port2Frequency::default(),
+        servo1: false,
+        servo2: false,
+    };
+    let pwm = |channel| Some(SimPwm::new(channel, &trace));
+    let build = |motor_pwm| {
+        MotorShield::<SimBoard, _, _>::from_parts(layout(), SimShift::new(&trace), (), motor_pwm, [None, None])
+    };
+
+    let missing_motor = build([pwm(SimChannel::D11), None, pwm(SimChannel::D6), pwm(SimChannel::D5)]);
+    assert_eq!(missing_motor.err(), Some(ShieldError::MotorChannelMissing(MotorId::M2)));
+
+    let missing_coil = build([pwm(SimChannel::D11), pwm(SimChannel::D3), pwm(SimChannel::D6), None]);
+    assert_eq!(missing_coil.err(), Some(ShieldError::StepperChannelMissing(StepperId::S2)));
+
+    let all = [pwm(SimChannel::D11), pwm(SimChannel::D3), pwm(SimChannel::D6), pwm(SimChannel::D5)];
+    let mut shield = build(all).unwrap();
+    trace.clear();
+    shield.m1().speed(10);
+    assert_eq!(trace.duties(SimChannel::D11), [10]);
+}
+
+#[test]
+fn servo_sequence_runs_on_a_typed_shield() {
+    const FRAMES: [Keyframe; 2] = [
+        Keyframe::new(Some(0), None, 0, Easing::Linear),
+        Keyframe::new(Some(90), None, 100, Easing::Linear),
+    ];
+
+    let trace = Trace::new();
+    let layout = ShieldLayout {
+        port1: TwoMotors,
+        port2: Empty,
+        frequency1: Port1Frequency::default(),
+        frequency2: Port2Frequency::default(),
+        servo1: true,
+        servo2: false,
+    };
+    let mut shield: MotorShield<SimBoard, TwoMotors, Empty> = MotorShield::simulated(layout, &trace);
+    let mut sequence = ServoSequence::new(&FRAMES);
+
+    assert!(sequence.update(&mut shield, 50));
+    assert_eq!(shield.servo(ServoId::S1).unwrap().pulse_us(), Some(1250));
+    assert!(!sequence.update(&mut shield, 50));
+    assert_eq!(shield.servo(ServoId::S1).unwrap().pulse_us(), Some(1500));
+}
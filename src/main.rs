@@ -3,11 +3,44 @@
 
 use arduino_hal::Adc;
 use arduino_hal::hal::wdt;
-use panic_halt as _;
 use motor_shield::{init_ams};
 use motor_shield::ShieldLayout;
 use motor_shield::MotorShield;
-use motor_shield::MotorPort;
+use motor_shield::{Empty, Port1Frequency, Port2Frequency, TwoMotors};
+use motor_shield::MotorId;
+
+// Blinks per round of the error code D13 repeats after a panic.
+const PANIC_BLINKS: u8 = 3;
+
+// Stops the robot before anything else, then keeps signalling on D13 until
+// someone resets the board.
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    avr_device::interrupt::disable();
+    MotorShield::emergency_stop();
+
+    // SAFETY: nothing else runs once interrupts are off and `main` has been
+    // abandoned.
+    let dp = unsafe { arduino_hal::Peripherals::steal() };
+    let pins = arduino_hal::pins!(dp);
+
+    // The watchdog would otherwise reset the board and drive off again.
+    let mut watchdog = wdt::Wdt::new(dp.WDT, &dp.CPU.mcusr);
+    watchdog.stop();
+
+    let mut led = pins.d13.into_output();
+
+    loop {
+        for _ in 0..PANIC_BLINKS {
+            led.set_high();
+            arduino_hal::delay_ms(400);
+            led.set_low();
+            arduino_hal::delay_ms(400);
+        }
+
+        arduino_hal::delay_ms(1600);
+    }
+}
 
 #[arduino_hal::entry]
 fn main() -> ! {
@@ -22,10 +55,15 @@ fn main() -> ! {
     let a4 = pins.a4.into_analog_input(&mut adc);
     let a5 = pins.a5.into_analog_input(&mut adc);
 
-    let mut motor_shield = init_ams!(
+    // Port 2 and the servo headers are unused; their timers and pins stay free.
+    let (mut motor_shield, _leftovers) = init_ams!(
         ShieldLayout {
-            port1: MotorPort::TwoMotors,
-            port2: MotorPort::Empty,
+            port1: TwoMotors,
+            port2: Empty,
+            frequency1: Port1Frequency::default(),
+            frequency2: Port2Frequency::default(),
+            servo1: false,
+            servo2: false,
         },
         dp,
         pins
@@ -34,7 +72,11 @@ fn main() -> ! {
     let mut watchdog = wdt::Wdt::new(dp.WDT, &dp.CPU.mcusr);
     watchdog.start(wdt::Timeout::Ms4000).unwrap();
 
-    motor_shield.enable_motors(&[0, 1]);
+    motor_shield.enable_motors(&[MotorId::M1, MotorId::M2]).unwrap();
+
+    // Ramp over a few dozen loop passes instead of jumping to full duty.
+    motor_shield.m1().set_acceleration(Some(8));
+    motor_shield.m2().set_acceleration(Some(8));
 
     loop {
         let infra: [u16; 6] = [
@@ -50,13 +92,16 @@ fn main() -> ! {
         let center = infra[2] + infra[3];
         let right = infra[4] + infra[5];
 
-        if left > center && left > right {
-            motor_shield.set_speeds(&[(255, 0), (0, 1)]);
+        let velocities = if left > center && left > right {
+            [(MotorId::M1, 255), (MotorId::M2, 0)]
         } else if right > center {
-            motor_shield.set_speeds(&[(0, 255), (0, 1)]);
+            [(MotorId::M1, 0), (MotorId::M2, 255)]
         } else {
-            motor_shield.set_speeds(&[(255, 255), (0, 1)]);
-        }
+            [(MotorId::M1, 255), (MotorId::M2, 255)]
+        };
+        // Both motors are in the layout above, so this cannot fail.
+        let _ = motor_shield.set_velocities(&velocities);
+        motor_shield.update(1);
         watchdog.feed();
     }
 }